@@ -0,0 +1,192 @@
+use crate::steering::Steering;
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+// Structures & enums
+
+/// Tuning for [`ArrivalController`]. Gains are in force units, so heavier
+/// floaters need larger ones.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArrivalSettings {
+    pub proportional: f32,
+    pub integral: f32,
+    pub derivative: f32,
+    pub max_force: f32,
+    /// How close counts as arrived.
+    pub arrival_radius: f32,
+    /// Below this speed inside `arrival_radius` the floater is at rest.
+    pub arrival_speed: f32,
+    /// Past this distance the pull stops growing, which caps cruising speed.
+    pub slowdown_radius: f32,
+}
+
+/// A PID controller that brings a floater to rest at its target.
+#[derive(Clone, Copy, Debug)]
+pub struct ArrivalController {
+    settings: ArrivalSettings,
+    integral: Vector2<f32>,
+}
+
+impl Default for ArrivalSettings {
+    fn default() -> Self {
+        ArrivalSettings {
+            proportional: 10.0,
+            integral: 1.0,
+            derivative: 5.0,
+            max_force: 1000.0,
+            arrival_radius: 2.0,
+            arrival_speed: 5.0,
+            slowdown_radius: 150.0,
+        }
+    }
+}
+
+impl ArrivalSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let gains = [self.proportional, self.integral, self.derivative];
+        if gains.iter().any(|gain| gain.is_nan() || *gain < 0.0) {
+            return Err("arrival gains can't be negative".to_string());
+        }
+        let limits = [
+            self.max_force,
+            self.arrival_radius,
+            self.arrival_speed,
+            self.slowdown_radius,
+        ];
+        if limits.iter().any(|limit| limit.is_nan() || *limit <= 0.0) {
+            return Err("arrival max_force, radii and arrival_speed must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ArrivalController {
+    pub fn new(settings: ArrivalSettings) -> Self {
+        ArrivalController {
+            settings,
+            integral: Vector2::zeros(),
+        }
+    }
+
+    pub fn settings(&self) -> ArrivalSettings {
+        self.settings
+    }
+
+    /// Forgets the accumulated error, e.g. when the target changes.
+    pub fn reset(&mut self) {
+        self.integral = Vector2::zeros();
+    }
+
+    /// The force to apply this step, or `Arrived` once the floater has come to
+    /// rest within `arrival_radius` of `target`.
+    pub fn steer(
+        &mut self,
+        position: Vector2<f32>,
+        velocity: Vector2<f32>,
+        target: Vector2<f32>,
+        dt: f32,
+    ) -> Steering {
+        let settings = self.settings;
+        let mut error = target - position;
+        let distance = error.norm();
+
+        if distance <= settings.arrival_radius && velocity.norm() <= settings.arrival_speed {
+            self.reset();
+            return Steering::Arrived;
+        }
+
+        if distance > settings.slowdown_radius {
+            // Only integrate once close, so the long approach doesn't wind it up.
+            error *= settings.slowdown_radius / distance;
+            self.reset();
+        } else if settings.integral > 0.0 {
+            // Drop what built up on the approach once past the target.
+            if self.integral.dot(&error) < 0.0 {
+                self.reset();
+            }
+            self.integral += error * dt;
+            let limit = settings.max_force / settings.integral;
+            if self.integral.norm() > limit {
+                self.integral = self.integral.normalize() * limit;
+            }
+        }
+
+        // The derivative works on velocity rather than the error, so a new
+        // target doesn't give the floater a kick.
+        let mut force = error * settings.proportional + self.integral * settings.integral
+            - velocity * settings.derivative;
+        if force.norm() > settings.max_force {
+            force = force.normalize() * settings.max_force;
+        }
+
+        Steering::Force(force)
+    }
+}
+
+impl Default for ArrivalController {
+    fn default() -> Self {
+        ArrivalController::new(ArrivalSettings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steers a unit mass from `start` until it arrives, returning how many
+    /// steps that took and the most force asked for on the way.
+    fn arrive(start: Vector2<f32>, target: Vector2<f32>) -> Option<(u32, f32)> {
+        let dt = 1.0 / 60.0;
+        let mut controller = ArrivalController::default();
+        let (mut position, mut velocity) = (start, Vector2::zeros());
+        let mut strongest: f32 = 0.0;
+        for step in 0..60 * 30 {
+            match controller.steer(position, velocity, target, dt) {
+                Steering::Arrived => return Some((step, strongest)),
+                Steering::Force(force) => {
+                    strongest = strongest.max(force.norm());
+                    velocity += force * dt;
+                    position += velocity * dt;
+                }
+                Steering::Idle => return None,
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn comes_to_rest_at_the_target() {
+        let (steps, strongest) =
+            arrive(Vector2::new(0.0, 0.0), Vector2::new(300.0, 200.0)).expect("Never arrived");
+        assert!(steps > 0);
+        assert!(strongest <= ArrivalSettings::default().max_force + 1e-3);
+    }
+
+    #[test]
+    fn already_at_rest_on_the_target_has_arrived() {
+        let mut controller = ArrivalController::default();
+        let target = Vector2::new(10.0, 10.0);
+        let steering = controller.steer(target, Vector2::zeros(), target, 1.0 / 60.0);
+        assert!(matches!(steering, Steering::Arrived));
+    }
+
+    #[test]
+    fn passing_through_the_target_is_not_arriving() {
+        let mut controller = ArrivalController::default();
+        let target = Vector2::new(10.0, 10.0);
+        let steering = controller.steer(target, Vector2::new(100.0, 0.0), target, 1.0 / 60.0);
+        assert!(matches!(steering, Steering::Force(_)));
+    }
+
+    #[test]
+    fn negative_gains_are_rejected() {
+        let settings = ArrivalSettings {
+            derivative: -1.0,
+            ..ArrivalSettings::default()
+        };
+        assert!(settings.validate().is_err());
+        assert!(ArrivalSettings::default().validate().is_ok());
+    }
+}
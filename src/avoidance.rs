@@ -0,0 +1,248 @@
+use crate::physics::PhysicsStruct;
+
+use nalgebra::Vector2;
+use nphysics2d::object::DefaultBodyHandle;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+// Constants
+const SAMPLE_DIRECTIONS: usize = 16;
+/// Candidate speeds, as fractions of the faster of the floater's current and
+/// preferred speed.
+const SAMPLE_SPEEDS: [f32; 3] = [1.0 / 3.0, 2.0 / 3.0, 1.0];
+
+// Structures & enums
+
+/// Tuning for reciprocal velocity obstacles. Each steering floater picks the
+/// velocity closest to the one its controller wants that doesn't run into a
+/// neighbour soon, assuming steering neighbours take half the effort.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AvoidanceSettings {
+    /// How far away other floaters count as neighbours.
+    pub neighbour_radius: f32,
+    /// Collisions further away than this, in seconds, are ignored.
+    pub time_horizon: f32,
+    /// How much speed off the preferred velocity is worth putting a
+    /// collision off by a second.
+    pub weight: f32,
+    /// Gap kept between floaters on top of their radii.
+    pub margin: f32,
+    /// Cap on the correction added to the controller's force.
+    pub max_force: f32,
+}
+
+/// A floater taking part in avoidance. `force` is what its controller wants
+/// this step; floaters that aren't steering still get steered around.
+pub struct Agent {
+    pub handle: DefaultBodyHandle,
+    pub radius: f32,
+    pub force: Option<Vector2<f32>>,
+}
+
+struct Neighbour {
+    offset: Vector2<f32>,
+    velocity: Vector2<f32>,
+    radius: f32,
+    /// How much of the avoiding this floater does itself.
+    share: f32,
+}
+
+impl Default for AvoidanceSettings {
+    fn default() -> Self {
+        AvoidanceSettings {
+            neighbour_radius: 100.0,
+            time_horizon: 1.5,
+            weight: 60.0,
+            margin: 2.0,
+            max_force: 1000.0,
+        }
+    }
+}
+
+/// Adjusts each steering agent's force to avoid its neighbours, returning the
+/// forces to apply. Agents without neighbours keep their force as is.
+pub fn avoid(
+    settings: &AvoidanceSettings,
+    physics: &PhysicsStruct,
+    agents: &[Agent],
+    dt: f32,
+) -> Vec<(DefaultBodyHandle, Vector2<f32>)> {
+    let by_handle: HashMap<DefaultBodyHandle, &Agent> =
+        agents.iter().map(|agent| (agent.handle, agent)).collect();
+    let reach = Vector2::new(settings.neighbour_radius, settings.neighbour_radius);
+
+    let mut forces = Vec::new();
+    for agent in agents.iter() {
+        let force = match agent.force {
+            Some(force) => force,
+            None => continue,
+        };
+        let position = physics.get_pos_of(agent.handle);
+        let velocity = physics.get_vel_of(agent.handle);
+
+        let neighbours: Vec<Neighbour> = physics
+            .bodies_in_aabb(position - reach, position + reach)
+            .into_iter()
+            .filter(|&handle| handle != agent.handle)
+            .filter_map(|handle| by_handle.get(&handle))
+            .map(|other| Neighbour {
+                offset: physics.get_pos_of(other.handle) - position,
+                velocity: physics.get_vel_of(other.handle),
+                radius: agent.radius + other.radius + settings.margin,
+                share: if other.force.is_some() { 0.5 } else { 1.0 },
+            })
+            .collect();
+        if neighbours.is_empty() {
+            forces.push((agent.handle, force));
+            continue;
+        }
+
+        let mass = physics.get_mass_of(agent.handle);
+        let preferred = velocity + force * dt / mass;
+        let penalty = |candidate: Vector2<f32>| {
+            let soonest = neighbours
+                .iter()
+                .map(|neighbour| {
+                    // Reciprocal: we only move our share of the way to
+                    // `candidate`, trusting the neighbour with the rest.
+                    let relative =
+                        velocity + (candidate - velocity) / neighbour.share - neighbour.velocity;
+                    time_to_collision(neighbour.offset, relative, neighbour.radius)
+                })
+                .fold(f32::INFINITY, f32::min);
+            let danger = if soonest < settings.time_horizon {
+                settings.weight / soonest.max(dt)
+            } else {
+                0.0
+            };
+            (candidate - preferred).norm() + danger
+        };
+
+        let speed = preferred.norm().max(velocity.norm());
+        let mut best = preferred;
+        let mut best_penalty = penalty(preferred);
+        let samples =
+            std::iter::once(Vector2::zeros()).chain((0..SAMPLE_DIRECTIONS).flat_map(|direction| {
+                let angle = 2.0 * PI * direction as f32 / SAMPLE_DIRECTIONS as f32;
+                SAMPLE_SPEEDS
+                    .iter()
+                    .map(move |&fraction| Vector2::new(angle.cos(), angle.sin()) * speed * fraction)
+            }));
+        for candidate in samples {
+            let candidate_penalty = penalty(candidate);
+            if candidate_penalty < best_penalty {
+                best = candidate;
+                best_penalty = candidate_penalty;
+            }
+        }
+
+        let mut correction = (best - preferred) * mass / dt;
+        if correction.norm() > settings.max_force {
+            correction = correction.normalize() * settings.max_force;
+        }
+        forces.push((agent.handle, force + correction));
+    }
+    forces
+}
+
+/// Seconds until a body at `offset` comes within `radius`, given the
+/// `relative` velocity toward it. Zero if already overlapping and closing.
+fn time_to_collision(offset: Vector2<f32>, relative: Vector2<f32>, radius: f32) -> f32 {
+    let closing = relative.dot(&offset);
+    let gap = offset.norm_squared() - radius * radius;
+    if gap < 0.0 {
+        return if closing > 0.0 { 0.0 } else { f32::INFINITY };
+    }
+
+    let speed = relative.norm_squared();
+    let discriminant = closing * closing - speed * gap;
+    if speed <= 0.0 || closing <= 0.0 || discriminant < 0.0 {
+        return f32::INFINITY;
+    }
+    (closing - discriminant.sqrt()) / speed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::Isometry2;
+    use nphysics2d::math::Velocity;
+
+    const RADIUS: f32 = 10.0;
+
+    /// A floater at `x` on a shared line, moving at `speed` along it.
+    fn floater(physics: &mut PhysicsStruct, x: f32, speed: f32) -> DefaultBodyHandle {
+        let handle = physics.create_ball(RADIUS, Vector2::new(x, 200.0), 0.003);
+        physics.set_state_of(
+            handle,
+            Isometry2::translation(x, 200.0),
+            Velocity::linear(speed, 0.0),
+        );
+        handle
+    }
+
+    #[test]
+    fn head_on_collision_time() {
+        let offset = Vector2::new(100.0, 0.0);
+        let time = time_to_collision(offset, Vector2::new(40.0, 0.0), 20.0);
+        assert!((time - 2.0).abs() < 1e-4);
+        assert!(time_to_collision(offset, Vector2::new(-40.0, 0.0), 20.0).is_infinite());
+        assert!(time_to_collision(offset, Vector2::new(0.0, 40.0), 20.0).is_infinite());
+    }
+
+    #[test]
+    fn head_on_agents_steer_clear() {
+        let mut physics = PhysicsStruct::new((400.0, 400.0));
+        let left = floater(&mut physics, 100.0, 60.0);
+        let right = floater(&mut physics, 300.0, -60.0);
+        physics.sync_geometry();
+
+        let dt = physics.timestep();
+        let settings = AvoidanceSettings::default();
+        let mut closest = f32::INFINITY;
+        for _ in 0..240 {
+            // Each keeps pushing straight at where the other started.
+            let agents = [
+                Agent {
+                    handle: left,
+                    radius: RADIUS,
+                    force: Some(Vector2::new(20.0, 0.0)),
+                },
+                Agent {
+                    handle: right,
+                    radius: RADIUS,
+                    force: Some(Vector2::new(-20.0, 0.0)),
+                },
+            ];
+            for (handle, force) in avoid(&settings, &physics, &agents, dt) {
+                physics.apply_force(handle, force);
+            }
+            physics.step();
+            let gap = (physics.get_pos_of(right) - physics.get_pos_of(left)).norm();
+            closest = closest.min(gap);
+        }
+
+        // Close enough to have had to avoid each other, but never touching.
+        assert!(closest < settings.neighbour_radius, "closest {}", closest);
+        assert!(closest > 2.0 * RADIUS, "closest {}", closest);
+    }
+
+    #[test]
+    fn lone_agents_keep_their_force() {
+        let mut physics = PhysicsStruct::new((400.0, 400.0));
+        let alone = floater(&mut physics, 100.0, 0.0);
+        physics.sync_geometry();
+        let force = Vector2::new(50.0, 0.0);
+        let agents = [Agent {
+            handle: alone,
+            radius: RADIUS,
+            force: Some(force),
+        }];
+        let forces = avoid(&AvoidanceSettings::default(), &physics, &agents, 1.0 / 60.0);
+        assert_eq!(forces, vec![(alone, force)]);
+    }
+}
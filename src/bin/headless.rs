@@ -0,0 +1,122 @@
+use rust_physics_workbench::cli::RunOptions;
+use rust_physics_workbench::headless;
+use rust_physics_workbench::{Scene, TopDownWorld};
+
+use nalgebra::Vector2;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use std::env;
+use std::fs::File;
+use std::io;
+use std::process;
+
+const USAGE: &str = "usage: headless [SCENE | --scene FILE] [--ticks N] [--rate HZ] [--seed N] \
+                     [--target X,Y] [--out FILE]";
+
+// Structures & enums
+
+struct Options {
+    /// The options every binary shares. Recording, replaying and the
+    /// substep cap aren't supported here.
+    run: RunOptions,
+    ticks: u32,
+    target: Option<Vector2<f32>>,
+    out: Option<String>,
+}
+
+impl Options {
+    fn from_args<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options {
+            run: RunOptions::default(),
+            ticks: 600,
+            target: None,
+            out: None,
+        };
+
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            if options.run.parse_arg(&arg, &mut args)? {
+                continue;
+            }
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--ticks" => {
+                    options.ticks = value()?
+                        .parse()
+                        .map_err(|_| "--ticks expects a whole number".to_string())?;
+                }
+                "--target" => options.target = Some(parse_point(&value()?)?),
+                "--out" => options.out = Some(value()?),
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+
+        options.run.check_headless()?;
+        Ok(options)
+    }
+}
+
+fn parse_point(text: &str) -> Result<Vector2<f32>, String> {
+    let mut coords = text.split(',').map(|c| c.trim().parse::<f32>());
+    match (coords.next(), coords.next(), coords.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Ok(Vector2::new(x, y)),
+        _ => Err(format!("expected a point like 400,300 but got {}", text)),
+    }
+}
+
+fn main() {
+    let options = match Options::from_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let scene = match options.run.load_scene(Scene::top_down) {
+        Ok(scene) => scene,
+        Err(e) => {
+            eprintln!("Error loading scene: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let world = match options.run.seed {
+        Some(seed) => TopDownWorld::from_scene(&scene, &mut StdRng::seed_from_u64(seed)),
+        None => TopDownWorld::from_scene(&scene, &mut rand::thread_rng()),
+    };
+    let mut world = match world {
+        Ok(world) => world,
+        Err(e) => {
+            eprintln!("Error building world: {}", e);
+            process::exit(1);
+        }
+    };
+
+    if let Some(target) = options.target {
+        for floater in world.floaters.iter_mut() {
+            floater.set_target(target);
+        }
+    }
+
+    world.physics.set_timestep(1.0 / options.run.rate as f32);
+    headless::run(&mut world, options.ticks);
+
+    let states = headless::body_states(&world);
+    let result = match options.out {
+        Some(path) => {
+            File::create(path).and_then(|mut file| headless::write_csv(&states, &mut file))
+        }
+        None => headless::write_csv(&states, &mut io::stdout()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error writing body states: {}", e);
+        process::exit(1);
+    }
+}
@@ -1,44 +1,38 @@
 // ggez imports
 
+use ggez::conf::WindowMode;
 use ggez::event::{self, EventHandler, KeyCode, KeyMods};
-use ggez::nalgebra as na;
-use ggez::{graphics, Context, ContextBuilder, GameResult};
+use ggez::{graphics, timer, Context, ContextBuilder, GameResult};
 
 // nphysics imports
 
 use nalgebra::Vector2;
-use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
-use nphysics2d::force_generator::DefaultForceGeneratorSet;
-use nphysics2d::joint::DefaultJointConstraintSet;
-use nphysics2d::math::{Force, ForceType, Velocity};
-use nphysics2d::object::{
-    Body, BodyPartHandle, ColliderDesc, DefaultBodyHandle, DefaultBodySet, DefaultColliderSet,
-    Ground, RigidBody, RigidBodyDesc,
-};
-use nphysics2d::world::{DefaultGeometricalWorld, DefaultMechanicalWorld};
+use nphysics2d::object::DefaultBodyHandle;
 
-// Constants
+use rust_physics_workbench::cli::{RunOptions, RUN_USAGE};
+use rust_physics_workbench::draw::{draw_physics_ball, draw_prop, draw_text, HUD_COLOR};
+use rust_physics_workbench::replay::{InputLog, Replay};
+use rust_physics_workbench::scene::{Role, SceneBody};
+use rust_physics_workbench::snapshot::{self, BallSnapshot, PhysicsSnapshot, PropSnapshot};
+use rust_physics_workbench::time_control::TimeControl;
+use rust_physics_workbench::timestep::{FixedTimestep, Interpolation};
+use rust_physics_workbench::{PhysicsStruct, Scene, SceneError, SnapshotError, BLUE, GREEN};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
-const BLUE: graphics::Color = graphics::Color {
-    r: 0.01,
-    g: 0.33,
-    b: 0.98,
-    a: 1.0,
-};
+use serde::{Deserialize, Serialize};
 
-const GREEN: graphics::Color = graphics::Color {
-    r: 0.01,
-    g: 0.98,
-    b: 0.43,
-    a: 1.0,
-};
+use std::collections::HashMap;
+use std::env;
 
-const BALL_RAD: f32 = 20.0;
-const WIN_WIDTH: f32 = 800.0;
-const WIN_HEIGHT: f32 = 600.0;
+// Constants
+
+const QUICKSAVE_PATH: &str = "side_view.quicksave.ron";
 
 // Structures & enums
 
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 enum Action {
     Up,
     Left,
@@ -46,6 +40,8 @@ enum Action {
     Hover,
     GravityUp,
     GravityDown,
+    QuickSave,
+    QuickLoad,
 }
 
 impl Action {
@@ -57,152 +53,274 @@ impl Action {
             KeyCode::C => Some(Action::Hover),
             KeyCode::PageUp => Some(Action::GravityUp),
             KeyCode::PageDown => Some(Action::GravityDown),
+            KeyCode::F5 => Some(Action::QuickSave),
+            KeyCode::F9 => Some(Action::QuickLoad),
             _ => None,
         }
     }
 }
 
-struct PhysicsStruct {
-    mechanical_world: DefaultMechanicalWorld<f32>,
-    geometrical_world: DefaultGeometricalWorld<f32>,
-    bodies: DefaultBodySet<f32>,
-    colliders: DefaultColliderSet<f32>,
-    joint_constraints: DefaultJointConstraintSet<f32>,
-    force_generators: DefaultForceGeneratorSet<f32>,
+#[derive(Serialize, Deserialize)]
+struct SideViewSnapshot {
+    physics: PhysicsSnapshot,
+    ball: BallSnapshot,
+    props: Vec<PropSnapshot>,
+    hover_on: bool,
+    #[serde(default)]
+    tick: u64,
 }
 
 struct MyGame {
     physics: PhysicsStruct,
     ball: DefaultBodyHandle,
+    ball_radius: f32,
+    ball_color: graphics::Color,
+    props: Vec<SceneBody>,
     hover_on: bool,
+    tick: u64,
+    recording: Option<(String, InputLog<Action>)>,
+    replay: Option<Replay<Action>>,
+    timestep: FixedTimestep,
+    interpolation: Interpolation,
+    time_control: TimeControl,
 }
 
 impl MyGame {
-    pub fn new(_ctx: &mut Context) -> MyGame {
+    pub fn new(
+        _ctx: &mut Context,
+        scene: &Scene,
+        seed: u64,
+        timestep: FixedTimestep,
+    ) -> Result<MyGame, SceneError> {
         // Load/create resources such as images here.
 
-        let mechanical_world = DefaultMechanicalWorld::new(Vector2::new(0.0, 300.0));
-        let geometrical_world = DefaultGeometricalWorld::new();
-        let mut bodies = DefaultBodySet::new();
-        let mut colliders = DefaultColliderSet::new();
-        let joint_constraints = DefaultJointConstraintSet::new();
-        let force_generators = DefaultForceGeneratorSet::new();
+        if scene.bodies_with_role(Role::Player) != 1 {
+            return Err(SceneError::invalid(
+                None,
+                "a side_view scene needs exactly one Player".to_string(),
+            ));
+        }
+        if scene
+            .bodies
+            .iter()
+            .any(|body| !matches!(body.role, Role::Player | Role::Prop))
+        {
+            return Err(SceneError::invalid(
+                None,
+                "side_view scenes only support Player and Prop bodies".to_string(),
+            ));
+        }
+
+        let (mut physics, bodies) =
+            PhysicsStruct::from_scene(scene, &mut StdRng::seed_from_u64(seed))?;
+        physics.set_timestep(timestep.dt());
 
         // Ball
 
-        let ball_shape = ShapeHandle::new(Ball::new(BALL_RAD));
-        let rigid_body = RigidBodyDesc::new()
-            .translation(Vector2::new(300.0, 300.0))
-            // .velocity(Velocity::linear(300.0, -200.0))
-            // .linear_damping(1.0)
-            .build();
-
-        let ball = bodies.insert(rigid_body);
-        let co = ColliderDesc::new(ball_shape.clone())
-            .density(0.005)
-            .build(BodyPartHandle(ball, 0));
-        colliders.insert(co);
-
-        // Ground
-
-        let ground_size = WIN_WIDTH;
-        let ground_shape = ShapeHandle::new(Cuboid::new(Vector2::new(ground_size, 1.0)));
-
-        let ground_handle = bodies.insert(Ground::new());
-        let co = ColliderDesc::new(ground_shape)
-            .translation(Vector2::new(0.0, WIN_HEIGHT))
-            .build(BodyPartHandle(ground_handle, 0));
-        colliders.insert(co);
-
-        // Walls
-
-        let wall_size = WIN_HEIGHT;
-        let wall_shape = ShapeHandle::new(Cuboid::new(Vector2::new(1.0, wall_size)));
-        let wall_handle_l = bodies.insert(Ground::new());
-        let left_wall_co = ColliderDesc::new(wall_shape.clone())
-            .translation(Vector2::new(0.0, 0.0))
-            .build(BodyPartHandle(wall_handle_l, 0));
-        let wall_handle_r = bodies.insert(Ground::new());
-        let right_wall_co = ColliderDesc::new(wall_shape.clone())
-            .translation(Vector2::new(WIN_WIDTH, 0.0))
-            .build(BodyPartHandle(wall_handle_r, 0));
-        colliders.insert(left_wall_co);
-        colliders.insert(right_wall_co);
-
-        // Return val
-
-        let physics = PhysicsStruct {
-            mechanical_world,
-            geometrical_world,
-            bodies,
-            colliders,
-            joint_constraints,
-            force_generators,
-        };
+        let (props, players): (Vec<SceneBody>, Vec<SceneBody>) =
+            bodies.into_iter().partition(|body| body.role == Role::Prop);
+        let player = players[0];
 
-        MyGame {
+        Ok(MyGame {
             physics,
-            ball,
+            ball: player.handle,
+            ball_radius: player.shape.bounding_radius(),
+            ball_color: player.color.unwrap_or(BLUE),
+            props,
             hover_on: false,
+            tick: 0,
+            recording: None,
+            replay: None,
+            timestep,
+            interpolation: Interpolation::default(),
+            time_control: TimeControl::new(),
+        })
+    }
+
+    fn step(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            let due = replay.take_due(self.tick);
+            if replay.is_finished() {
+                self.replay = None;
+                println!("Replay finished at tick {}", self.tick);
+            }
+            for act in due {
+                self.apply_action(act);
+            }
         }
+
+        if self.hover_on {
+            let hover_force: f32 = -1875.0;
+            self.physics
+                .apply_force(self.ball, Vector2::new(0.0, hover_force));
+        }
+
+        self.interpolation.capture(&self.physics);
+        self.physics.step();
+        self.tick += 1;
     }
 
-    fn get_ball(&self) -> &RigidBody<f32> {
-        self.physics
-            .bodies
-            .rigid_body(self.ball)
-            .expect("Ball not found")
+    fn apply_action(&mut self, act: Action) {
+        let impulse: f32 = 1000.0;
+        let force_up = Vector2::new(0.0, -impulse);
+        let force_left = Vector2::new(-impulse, 0.0);
+        let force_right = Vector2::new(impulse, 0.0);
+
+        match act {
+            Action::Up => {
+                self.physics.apply_impulse(self.ball, force_up);
+            }
+            Action::Left => {
+                self.physics.apply_impulse(self.ball, force_left);
+            }
+            Action::Right => {
+                self.physics.apply_impulse(self.ball, force_right);
+            }
+            Action::Hover => {
+                self.hover_on = !self.hover_on;
+            }
+            Action::GravityUp => {
+                let gravity = self.physics.gravity();
+                self.physics.set_gravity(gravity + Vector2::new(0.0, 10.0));
+            }
+            Action::GravityDown => {
+                let gravity = self.physics.gravity();
+                self.physics.set_gravity(gravity + Vector2::new(0.0, -10.0));
+            }
+            Action::QuickSave => self.quick_save(),
+            Action::QuickLoad => self.quick_load(),
+        }
     }
 
-    fn get_ball_mut(&mut self) -> &mut RigidBody<f32> {
-        self.physics
-            .bodies
-            .rigid_body_mut(self.ball)
-            .expect("Ball not found")
+    fn save_recording(&self) {
+        if let Some((path, log)) = &self.recording {
+            match snapshot::save(path, log) {
+                Ok(()) => println!("Saved {} recorded inputs to {}", log.inputs.len(), path),
+                Err(e) => println!("Error saving recording: {}", e),
+            }
+        }
+    }
+
+    fn snapshot(&self) -> SideViewSnapshot {
+        let (physics, handles) = self.physics.snapshot();
+        let indices: HashMap<DefaultBodyHandle, usize> = handles
+            .into_iter()
+            .enumerate()
+            .map(|(index, handle)| (handle, index))
+            .collect();
+
+        SideViewSnapshot {
+            physics,
+            ball: BallSnapshot {
+                body: indices[&self.ball],
+                radius: self.ball_radius,
+                color: self.ball_color.into(),
+            },
+            props: self
+                .props
+                .iter()
+                .map(|prop| PropSnapshot {
+                    body: indices[&prop.handle],
+                    role: prop.role,
+                    shape: prop.shape,
+                    color: prop.color.map(|color| color.into()),
+                })
+                .collect(),
+            hover_on: self.hover_on,
+            tick: self.tick,
+        }
+    }
+
+    fn restore(&mut self, snapshot: &SideViewSnapshot) -> Result<(), SnapshotError> {
+        let (physics, handles) = PhysicsStruct::from_snapshot(&snapshot.physics)?;
+        let ball = handles[snapshot.physics.check_body(snapshot.ball.body)?];
+        let mut props = Vec::new();
+        for saved in snapshot.props.iter() {
+            props.push(SceneBody {
+                handle: handles[snapshot.physics.check_body(saved.body)?],
+                role: saved.role,
+                shape: saved.shape,
+                color: saved.color.map(|color| color.into()),
+                arrival: None,
+            });
+        }
+
+        self.physics = physics;
+        self.physics.set_timestep(self.timestep.dt());
+        self.interpolation.clear();
+        self.ball = ball;
+        self.ball_radius = snapshot.ball.radius;
+        self.ball_color = snapshot.ball.color.into();
+        self.props = props;
+        self.hover_on = snapshot.hover_on;
+        self.tick = snapshot.tick;
+        Ok(())
+    }
+
+    fn quick_save(&self) {
+        match snapshot::save(QUICKSAVE_PATH, &self.snapshot()) {
+            Ok(()) => println!("Saved snapshot to {}", QUICKSAVE_PATH),
+            Err(e) => println!("Error saving snapshot: {}", e),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        if self.recording.is_some() || self.replay.is_some() {
+            println!("Quick-load is disabled while recording or replaying");
+            return;
+        }
+
+        let loaded = snapshot::load::<SideViewSnapshot, _>(QUICKSAVE_PATH)
+            .and_then(|snapshot| self.restore(&snapshot));
+        match loaded {
+            Ok(()) => println!("Loaded snapshot from {}", QUICKSAVE_PATH),
+            Err(e) => println!("Error loading snapshot: {}", e),
+        }
     }
 }
 
 impl EventHandler for MyGame {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
         // Update code here...
-        if self.hover_on {
-            let hover_force: f32 = -1875.0;
-            let ball_body = self.get_ball_mut();
-            ball_body.apply_force(
-                0,
-                &Force::new(Vector2::new(0.0, hover_force), 0.0),
-                ForceType::Force,
-                true,
-            );
+        let frame_time = self.time_control.scale(timer::delta(ctx));
+        let steps = self.timestep.advance(frame_time) + self.time_control.take_single_steps();
+        for _ in 0..steps {
+            self.step();
         }
 
-        self.physics.mechanical_world.step(
-            &mut self.physics.geometrical_world,
-            &mut self.physics.bodies,
-            &mut self.physics.colliders,
-            &mut self.physics.joint_constraints,
-            &mut self.physics.force_generators,
-        );
-
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx, graphics::WHITE);
         // Draw code here...
-        let ball_body = self.get_ball();
-        let pos = ball_body.position();
-        let translation = pos.translation.vector;
-        let circle = graphics::Mesh::new_circle(
+        for prop in self.props.iter() {
+            draw_prop(ctx, &self.physics, prop)?;
+        }
+
+        let alpha = if self.time_control.is_paused() {
+            1.0
+        } else {
+            self.timestep.alpha()
+        };
+        let translation = self.interpolation.pos_of(&self.physics, self.ball, alpha);
+        draw_physics_ball(
             ctx,
-            graphics::DrawMode::fill(),
-            na::Point2::new(translation[0], translation[1]),
-            BALL_RAD,
-            0.001,
-            if self.hover_on { GREEN } else { BLUE },
+            self.ball_radius,
+            &translation,
+            if self.hover_on {
+                GREEN
+            } else {
+                self.ball_color
+            },
         )?;
 
-        graphics::draw(ctx, &circle, (na::Point2::new(0.0, 0.0),))?;
+        draw_text(
+            ctx,
+            &self.time_control.label(self.tick),
+            (10.0, 10.0),
+            HUD_COLOR,
+        )?;
 
         graphics::present(ctx)
     }
@@ -214,61 +332,89 @@ impl EventHandler for MyGame {
         _keymods: KeyMods,
         _repeat: bool,
     ) {
-        let impulse: f32 = 1000.0;
-        let force_up = Vector2::new(0.0, -impulse);
-        let force_left = Vector2::new(-impulse, 0.0);
-        let force_right = Vector2::new(impulse, 0.0);
+        if self.time_control.handle_key(keycode) {
+            return;
+        }
 
-        if let Some(act) = Action::from_keycode(keycode) {
-            let ball_body = self.get_ball_mut();
-            match act {
-                Action::Up => {
-                    ball_body.apply_force(0, &Force::new(force_up, 0.0), ForceType::Impulse, true);
-                }
-                Action::Left => {
-                    ball_body.apply_force(
-                        0,
-                        &Force::new(force_left, 0.0),
-                        ForceType::Impulse,
-                        true,
-                    );
-                }
-                Action::Right => {
-                    ball_body.apply_force(
-                        0,
-                        &Force::new(force_right, 0.0),
-                        ForceType::Impulse,
-                        true,
-                    );
-                }
-                Action::Hover => {
-                    self.hover_on = !self.hover_on;
-                }
-                Action::GravityUp => {
-                    self.physics.mechanical_world.gravity += Vector2::new(0.0, 10.0);
-                }
-                Action::GravityDown => {
-                    self.physics.mechanical_world.gravity += Vector2::new(0.0, -10.0);
+        match Action::from_keycode(keycode) {
+            Some(act @ Action::QuickSave) | Some(act @ Action::QuickLoad) => self.apply_action(act),
+            // Live input would make the replay diverge from the recording.
+            Some(_) if self.replay.is_some() => (),
+            Some(act) => {
+                if let Some((_, log)) = &mut self.recording {
+                    log.record(self.tick, act);
                 }
+                self.apply_action(act);
             }
+            None => (),
         }
     }
 }
 
 fn main() {
+    let options = match RunOptions::from_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\nusage: side_view {}", e, RUN_USAGE);
+            return;
+        }
+    };
+
+    // A replay brings its own scene and seed, otherwise load the scene,
+    // falling back to the built-in one.
+    let replay_log = match &options.replay {
+        Some(path) => match snapshot::load::<InputLog<Action>, _>(path) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                println!("Error loading {}: {}", path, e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let (scene, seed, rate) = match &replay_log {
+        Some(log) => (log.scene.clone(), log.seed, log.rate),
+        None => match options.load_scene(Scene::side_view) {
+            Ok(scene) => (
+                scene,
+                options.seed.unwrap_or_else(rand::random),
+                options.rate,
+            ),
+            Err(e) => {
+                println!("Error loading scene: {}", e);
+                return;
+            }
+        },
+    };
+    println!("Seed: {}", seed);
+
     // Make a Context.
     let (mut ctx, mut event_loop) = ContextBuilder::new("my_game", "Cool Game Author")
+        .window_mode(WindowMode::default().dimensions(scene.arena.width, scene.arena.height))
         .build()
         .expect("aieee, could not create ggez context!");
 
     // Create an instance of your event handler.
     // Usually, you should provide it with the Context object to
     // use when setting your game up.
-    let mut my_game = MyGame::new(&mut ctx);
+    let timestep = FixedTimestep::new(rate, options.substep_cap());
+    let mut my_game = match MyGame::new(&mut ctx, &scene, seed, timestep) {
+        Ok(my_game) => my_game,
+        Err(e) => {
+            println!("Error building world: {}", e);
+            return;
+        }
+    };
+    my_game.replay = replay_log.map(Replay::new);
+    my_game.recording = options
+        .record
+        .map(|path| (path, InputLog::new(seed, scene, rate)));
 
     // Run!
     match event::run(&mut ctx, &mut event_loop, &mut my_game) {
         Ok(_) => println!("Exited cleanly."),
         Err(e) => println!("Error occured: {}", e),
     }
+
+    my_game.save_recording();
 }
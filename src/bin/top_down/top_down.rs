@@ -1,94 +1,509 @@
-mod floater;
-mod physics;
+use rust_physics_workbench::avoidance::AvoidanceSettings;
+use rust_physics_workbench::camera::Camera;
+use rust_physics_workbench::cli::{RunOptions, RUN_USAGE};
+use rust_physics_workbench::draw::{
+    draw_goal, draw_nav_grid, draw_physics_ball, draw_prop, draw_ring, draw_text, draw_timeline,
+    draw_zone, HUD_COLOR,
+};
+use rust_physics_workbench::formation::{Formation, FormationMove};
+use rust_physics_workbench::replay::{InputLog, Replay};
+use rust_physics_workbench::rewind::{Frame, RewindBuffer};
+use rust_physics_workbench::scene::ShapeDesc;
+use rust_physics_workbench::selection::{SelectMode, Selection};
+use rust_physics_workbench::snapshot::{self, TopDownSnapshot};
+use rust_physics_workbench::soccer::Match;
+use rust_physics_workbench::spatial::QueryFilter;
+use rust_physics_workbench::steering::{ControllerKind, Flee, Flock, Pursue, Seek, WanderSettings};
+use rust_physics_workbench::time_control::TimeControl;
+use rust_physics_workbench::timestep::{FixedTimestep, Interpolation};
+use rust_physics_workbench::{Orders, PhysicsBall, Scene, TopDownWorld, GREEN};
 
-use floater::{BeachBall, Floater, PhysicsBall};
-use physics::PhysicsStruct;
-
-use ggez::event::{self, EventHandler};
-use ggez::input::mouse::MouseButton;
+use ggez::conf::WindowMode;
+use ggez::event::{self, EventHandler, KeyCode, KeyMods};
+use ggez::input::keyboard;
+use ggez::input::mouse::{self, MouseButton};
 use ggez::nalgebra as na;
-use ggez::{graphics, Context, ContextBuilder, GameResult};
+use ggez::{graphics, timer, Context, ContextBuilder, GameResult};
 use ggez::mint::Point2;
 
 use nalgebra::Vector2;
 use nphysics2d::object::DefaultBodyHandle;
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use serde::{Deserialize, Serialize};
 
 use std::collections::HashSet;
+use std::env;
+use std::time::Duration;
 
 // Constants
-const WIN_WIDTH: f32 = 800.0;
-const WIN_HEIGHT: f32 = 600.0;
-const TOLERANCE: f32 = 0.001;
-const FLOATER_CNT: u32 = 15;
 const LINE_COLOR: graphics::Color = graphics::Color::new(0.99, 0.1, 0.99, 0.8);
+const BAND_COLOR: graphics::Color = graphics::Color::new(0.01, 0.98, 0.43, 0.2);
+const BAND_OUTLINE_COLOR: graphics::Color = graphics::Color::new(0.01, 0.6, 0.25, 0.9);
+// Drags shorter than this, in pixels, count as clicks.
+const DRAG_THRESHOLD: f32 = 4.0;
+const QUICKSAVE_PATH: &str = "top_down.quicksave.ron";
+const REWIND_SECONDS: u32 = 10;
+const REWIND_SPEED: usize = 2;
+const DOUBLE_TAP: Duration = Duration::from_millis(300);
+const OBSTACLE_HALF_SIZE: f32 = 20.0;
 
 // Structures & enums
 
+/// Everything besides the moving bodies that rewinding has to put back.
+struct SavedOrders {
+    floaters: Vec<(Option<Vector2<f32>>, Orders, ControllerKind)>,
+    formations: Vec<FormationMove>,
+    soccer: Option<Match>,
+    avoidance: Option<AvoidanceSettings>,
+    selection: Selection,
+    /// Who was in each zone, in zone order.
+    zones: Vec<Vec<DefaultBodyHandle>>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+enum Input {
+    Select {
+        x: f32,
+        y: f32,
+        #[serde(default = "legacy_select_mode")]
+        mode: SelectMode,
+    },
+    SelectBox {
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+        mode: SelectMode,
+    },
+    Command {
+        x: f32,
+        y: f32,
+        // Commands used to clear the selection, and older recordings rely on it.
+        #[serde(default)]
+        keep_selection: bool,
+        /// Append a waypoint instead of replacing the floater's orders.
+        #[serde(default)]
+        queue: bool,
+        /// Groups spread out into this formation rather than all heading
+        /// for the same point.
+        #[serde(default)]
+        formation: Option<Formation>,
+        #[serde(default)]
+        facing: Option<(f32, f32)>,
+    },
+    Patrol {
+        on: bool,
+    },
+    Hold,
+    Assign {
+        controller: ControllerKind,
+    },
+    AssignGroup {
+        group: usize,
+    },
+    RecallGroup {
+        group: usize,
+    },
+    /// Places an obstacle at the point, or removes the one already there.
+    Obstacle {
+        x: f32,
+        y: f32,
+    },
+    Avoidance {
+        on: bool,
+    },
+    /// The selected floaters push the beach ball to the point.
+    Herd {
+        x: f32,
+        y: f32,
+        queue: bool,
+    },
+}
+
+/// A left-button drag in progress, for box selection.
+struct Drag {
+    start: Vector2<f32>,
+    end: Vector2<f32>,
+    mode: SelectMode,
+}
+
+/// A right-button drag in progress. The order goes to `start`, and dragging
+/// away from it picks the way the formation faces.
+struct OrderDrag {
+    start: Vector2<f32>,
+    end: Vector2<f32>,
+    queue: bool,
+    /// Ctrl was held: move the beach ball to `start` instead.
+    herd: bool,
+}
+
 struct MyGame {
-    physics: PhysicsStruct,
-    floaters: Vec<Floater>,
-    selected: HashSet<DefaultBodyHandle>,
-    beach_ball: BeachBall,
+    world: TopDownWorld,
+    selection: Selection,
+    recording: Option<(String, InputLog<Input>)>,
+    replay: Option<Replay<Input>>,
+    timestep: FixedTimestep,
+    interpolation: Interpolation,
+    time_control: TimeControl,
+    rewind: RewindBuffer<SavedOrders>,
+    scrubbing: bool,
+    drag: Option<Drag>,
+    order_drag: Option<OrderDrag>,
+    formation: Formation,
+    show_navigation: bool,
+    show_zones: bool,
+    camera: Camera,
+    // The last group recalled and when, to spot double taps.
+    last_recall: Option<(usize, Duration)>,
 }
 
 impl MyGame {
-    pub fn new(_ctx: &mut Context) -> MyGame {
+    pub fn new(_ctx: &mut Context, mut world: TopDownWorld, timestep: FixedTimestep) -> MyGame {
         // Load/create resources such as images here.
-        let mut physics = PhysicsStruct::new((WIN_WIDTH, WIN_HEIGHT));
-
-        // Floater(s)
-        let mut floaters = Vec::new();
-        let mut rng = rand::thread_rng();
-        for _ in 0..FLOATER_CNT {
-            let handle = physics.create_ball(
-                Floater::size(),
-                Vector2::new(
-                    rng.gen_range(0.0, WIN_WIDTH),
-                    rng.gen_range(0.0, WIN_HEIGHT),
-                ),
-                0.003,
-            );
-            let new_float = Floater::new(handle);
-            floaters.push(new_float);
+        world.physics.set_timestep(timestep.dt());
+        let (width, height) = world.physics.dims();
+        let camera = Camera::new(Vector2::new(width / 2.0, height / 2.0));
+        let mut rewind = RewindBuffer::new((timestep.rate() * REWIND_SECONDS) as usize);
+        let selection = Selection::new();
+        rewind.record(world.tick, &world.physics, saved_orders(&world, &selection));
+
+        MyGame {
+            world,
+            selection,
+            recording: None,
+            replay: None,
+            timestep,
+            interpolation: Interpolation::default(),
+            time_control: TimeControl::new(),
+            rewind,
+            scrubbing: false,
+            drag: None,
+            order_drag: None,
+            formation: Formation::Line,
+            show_navigation: false,
+            show_zones: false,
+            camera,
+            last_recall: None,
         }
+    }
 
-        let selected = HashSet::new();
+    fn step(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            let due = replay.take_due(self.world.tick);
+            if replay.is_finished() {
+                self.replay = None;
+                println!("Replay finished at tick {}", self.world.tick);
+            }
+            for input in due {
+                self.apply_input(input);
+            }
+        }
 
-        let beach_ball_handle = physics.create_ball(
-            BeachBall::size(),
-            Vector2::new(WIN_WIDTH / 2.0, WIN_HEIGHT / 2.0),
-            0.0001,
+        self.interpolation.capture(&self.world.physics);
+        self.world.update();
+        self.rewind.record(
+            self.world.tick,
+            &self.world.physics,
+            saved_orders(&self.world, &self.selection),
         );
-        let beach_ball = BeachBall::new(beach_ball_handle);
+    }
 
-        MyGame {
-            physics,
-            floaters,
-            selected,
-            beach_ball,
+    /// Rewinding would make a recording or replay diverge from what
+    /// actually happened, so it's only allowed in free play.
+    fn can_rewind(&self) -> bool {
+        self.recording.is_none() && self.replay.is_none()
+    }
+
+    fn scrub_to(&mut self, ctx: &Context, x: f32) {
+        let bar = timeline_rect(ctx);
+        let x = x.max(bar.x).min(bar.x + bar.w);
+        let fraction = (x - bar.x) / bar.w;
+        let index = (fraction * self.rewind.capacity() as f32) as usize;
+        if let Some(frame) = self.rewind.seek(index) {
+            restore_frame(&mut self.world, &mut self.selection, frame);
+            self.interpolation.clear();
+        }
+    }
+
+    fn apply_input(&mut self, input: Input) {
+        match input {
+            Input::Select { x, y, mode } => {
+                let clicked_pos = Vector2::new(x, y);
+                let filter = QueryFilter {
+                    skip_fixed: true,
+                    ..QueryFilter::default()
+                };
+                // Every floater under the click, as recorded replays expect.
+                let hits: Vec<DefaultBodyHandle> = self
+                    .world
+                    .physics
+                    .bodies_at(clicked_pos, &filter)
+                    .into_iter()
+                    // The computer's players aren't ours to order.
+                    .filter(|&handle| {
+                        let index = self
+                            .world
+                            .floaters
+                            .iter()
+                            .position(|floater| floater.get_handle() == handle);
+                        matches!(index, Some(index) if !self.world.is_ai_controlled(index))
+                    })
+                    .collect();
+                self.selection.select(mode, &hits);
+            }
+            Input::SelectBox {
+                x0,
+                y0,
+                x1,
+                y1,
+                mode,
+            } => {
+                let mins = Vector2::new(x0.min(x1), y0.min(y1));
+                let maxs = Vector2::new(x0.max(x1), y0.max(y1));
+                let floaters: HashSet<DefaultBodyHandle> = self
+                    .world
+                    .floaters
+                    .iter()
+                    .enumerate()
+                    .filter(|&(index, _)| !self.world.is_ai_controlled(index))
+                    .map(|(_, floater)| floater.get_handle())
+                    .collect();
+                let hits: Vec<DefaultBodyHandle> = self
+                    .world
+                    .physics
+                    .bodies_in_aabb(mins, maxs)
+                    .into_iter()
+                    .filter(|handle| floaters.contains(handle))
+                    .collect();
+                self.selection.select(mode, &hits);
+            }
+            Input::Command {
+                x,
+                y,
+                keep_selection,
+                queue,
+                formation,
+                facing,
+            } => {
+                let clicked_pos = Vector2::new(x, y);
+                let members = self.selected_indices();
+                // A move order ends any herding, queued or not.
+                for &index in members.iter() {
+                    self.world.stop_herding(index);
+                }
+                match formation {
+                    Some(formation) if members.len() > 1 => {
+                        let facing = facing.map(|(x, y)| Vector2::new(x, y));
+                        if queue {
+                            let slots = self.world.formation_slots(
+                                &members,
+                                clicked_pos,
+                                facing,
+                                formation,
+                            );
+                            for (index, slot) in slots {
+                                self.world.leave_formation(index);
+                                self.world.floaters[index].queue_waypoint(slot);
+                            }
+                        } else {
+                            self.world
+                                .move_in_formation(&members, clicked_pos, facing, formation);
+                        }
+                    }
+                    _ => {
+                        for index in members {
+                            self.world.leave_formation(index);
+                            let floater = &mut self.world.floaters[index];
+                            if queue {
+                                floater.queue_waypoint(clicked_pos);
+                            } else {
+                                floater.set_target(clicked_pos);
+                            }
+                        }
+                    }
+                }
+                if !keep_selection {
+                    self.selection.selected.clear();
+                }
+            }
+            Input::Assign { controller } => {
+                for (index, floater) in self.world.floaters.iter_mut().enumerate() {
+                    if self.selection.contains(floater.get_handle()) {
+                        floater.set_controller(controller.build(index as u64));
+                    }
+                }
+            }
+            Input::Patrol { on } => {
+                for floater in self.world.floaters.iter_mut() {
+                    if self.selection.contains(floater.get_handle()) {
+                        floater.set_patrol(on);
+                    }
+                }
+            }
+            Input::Hold => {
+                for index in self.selected_indices() {
+                    self.world.leave_formation(index);
+                    self.world.stop_herding(index);
+                    let floater = &mut self.world.floaters[index];
+                    if let Ok(position) = self.world.physics.try_get_pos_of(floater.get_handle()) {
+                        floater.hold_position(position);
+                    }
+                }
+            }
+            Input::AssignGroup { group } => self.selection.assign_group(group),
+            Input::RecallGroup { group } => self.selection.recall_group(group),
+            Input::Obstacle { x, y } => {
+                let point = Vector2::new(x, y);
+                if !self.world.remove_obstacle_at(point) {
+                    self.world.add_obstacle(
+                        point,
+                        ShapeDesc::Cuboid {
+                            half_width: OBSTACLE_HALF_SIZE,
+                            half_height: OBSTACLE_HALF_SIZE,
+                        },
+                    );
+                }
+                // Rewind frames only hold moving bodies, so going back past
+                // this would leave the obstacle out of step with the replay.
+                self.rewind.clear();
+            }
+            Input::Herd { x, y, queue } => {
+                let members = self.selected_indices();
+                self.world.herd_ball(&members, Vector2::new(x, y), queue);
+            }
+            Input::Avoidance { on } => {
+                self.world.avoidance = if on {
+                    Some(AvoidanceSettings::default())
+                } else {
+                    None
+                };
+            }
+        }
+    }
+
+    /// Indices of the selected floaters, in order.
+    fn selected_indices(&self) -> Vec<usize> {
+        self.world
+            .floaters
+            .iter()
+            .enumerate()
+            .filter(|(_, floater)| self.selection.contains(floater.get_handle()))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn record_and_apply(&mut self, input: Input) {
+        if let Some((_, log)) = &mut self.recording {
+            log.record(self.world.tick, input);
+        }
+        self.apply_input(input);
+    }
+
+    /// Selects `group`, centering the camera on it if it was recalled twice
+    /// in quick succession.
+    fn recall_group(&mut self, ctx: &Context, group: usize) {
+        self.record_and_apply(Input::RecallGroup { group });
+
+        let now = timer::time_since_start(ctx);
+        let double_tap = match self.last_recall {
+            Some((last, at)) => last == group && now - at <= DOUBLE_TAP,
+            None => false,
+        };
+        if double_tap {
+            // Members may have been removed since the group was made.
+            let positions: Vec<Vector2<f32>> = self
+                .selection
+                .group(group)
+                .iter()
+                .filter_map(|&handle| self.world.physics.try_get_pos_of(handle).ok())
+                .collect();
+            if !positions.is_empty() {
+                let sum = positions
+                    .iter()
+                    .fold(Vector2::zeros(), |sum, pos| sum + pos);
+                self.camera.center_on(sum / positions.len() as f32);
+            }
+            self.last_recall = None;
+        } else {
+            self.last_recall = Some((group, now));
+        }
+    }
+
+    fn save_recording(&self) {
+        if let Some((path, log)) = &self.recording {
+            match snapshot::save(path, log) {
+                Ok(()) => println!("Saved {} recorded inputs to {}", log.inputs.len(), path),
+                Err(e) => println!("Error saving recording: {}", e),
+            }
+        }
+    }
+
+    fn quick_save(&self) {
+        let snapshot = match self.world.snapshot(&self.selection) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                println!("Error taking snapshot: {}", e);
+                return;
+            }
+        };
+        match snapshot::save(QUICKSAVE_PATH, &snapshot) {
+            Ok(()) => println!("Saved snapshot to {}", QUICKSAVE_PATH),
+            Err(e) => println!("Error saving snapshot: {}", e),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        if self.recording.is_some() || self.replay.is_some() {
+            println!("Quick-load is disabled while recording or replaying");
+            return;
+        }
+
+        let loaded = snapshot::load::<TopDownSnapshot, _>(QUICKSAVE_PATH)
+            .and_then(|snapshot| TopDownWorld::from_snapshot(&snapshot));
+        match loaded {
+            Ok((world, selection)) => {
+                self.world = world;
+                self.world.physics.set_timestep(self.timestep.dt());
+                self.selection = selection;
+                self.interpolation.clear();
+                self.rewind.clear();
+                self.rewind.record(
+                    self.world.tick,
+                    &self.world.physics,
+                    saved_orders(&self.world, &self.selection),
+                );
+                println!("Loaded snapshot from {}", QUICKSAVE_PATH);
+            }
+            Err(e) => println!("Error loading snapshot: {}", e),
         }
     }
 }
 
 impl EventHandler for MyGame {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
         // Update code here...
-        let mut floaters_to_force = Vec::new();
-        for floater in self.floaters.iter() {
-            if let Some(target) = floater.target {
-                floaters_to_force.push((floater.get_handle().clone(), target));
+        if self.can_rewind() {
+            if keyboard::is_key_pressed(ctx, KeyCode::Back) {
+                if let Some(frame) = self.rewind.step_back(REWIND_SPEED) {
+                    restore_frame(&mut self.world, &mut self.selection, frame);
+                    self.interpolation.clear();
+                }
+                return Ok(());
+            }
+            if self.scrubbing {
+                return Ok(());
             }
         }
 
-        for (handle, target) in floaters_to_force {
-            let translation = self.physics.get_pos_of(handle);
-            self.physics.apply_force(handle, target - translation);
+        let frame_time = self.time_control.scale(timer::delta(ctx));
+        let steps = self.timestep.advance(frame_time) + self.time_control.take_single_steps();
+        for _ in 0..steps {
+            self.step();
         }
 
-        self.physics.step();
-
         Ok(())
     }
 
@@ -96,95 +511,525 @@ impl EventHandler for MyGame {
         graphics::clear(ctx, graphics::WHITE);
 
         // Draw code here...
-        for floater in self.floaters.iter() {
-            let pos = self.physics.get_pos_of(floater.get_handle());
+        self.camera.apply(ctx)?;
+        let alpha = if self.time_control.is_paused() {
+            1.0
+        } else {
+            self.timestep.alpha()
+        };
+        if let Some(soccer) = &self.world.soccer {
+            for goal in soccer.goals.iter() {
+                draw_goal(ctx, goal)?;
+            }
+        }
+        for prop in self.world.props.iter() {
+            draw_prop(ctx, &self.world.physics, prop)?;
+        }
+        if self.show_navigation {
+            draw_nav_grid(ctx, &self.world.navigation)?;
+        }
+        if self.show_zones {
+            for zone in self.world.zones.iter() {
+                draw_zone(ctx, zone)?;
+            }
+        }
+
+        for floater in self.world.floaters.iter() {
+            let pos = match self.interpolation.try_pos_of(
+                &self.world.physics,
+                floater.get_handle(),
+                alpha,
+            ) {
+                Ok(pos) => pos,
+                Err(_) => continue,
+            };
+            // Team colors stay put, so selected players get a ring instead.
+            let selected = self.selection.contains(floater.get_handle());
             draw_physics_ball(
                 ctx,
-                Floater::size(),
+                floater.get_radius(),
                 &pos,
-                if self.selected.contains(&floater.get_handle()) {
-                    floater::GREEN
+                if selected && floater.team.is_none() {
+                    GREEN
                 } else {
-                    Floater::color()
+                    floater.get_color()
                 },
             )?;
+            if selected && floater.team.is_some() {
+                draw_ring(ctx, floater.get_radius() + 3.0, &pos, HUD_COLOR)?;
+            }
+            if let Some(group) = self.selection.group_of(floater.get_handle()) {
+                let radius = floater.get_radius();
+                draw_text(
+                    ctx,
+                    &group.to_string(),
+                    (pos.x + radius * 0.7, pos.y - radius * 0.7 - 14.0),
+                    HUD_COLOR,
+                )?;
+            }
+
+            if floater.target.is_some() {
+                let mut points: Vec<Point2<f32>> = std::iter::once(&pos)
+                    .chain(floater.path.waypoints())
+                    .chain(floater.route())
+                    .map(|point| Point2 {
+                        x: point[0],
+                        y: point[1],
+                    })
+                    .collect();
+                // Close the loop back to the current target when patrolling.
+                let target = 1 + floater.path.waypoints().count();
+                if floater.orders.patrol && points.len() > target + 1 {
+                    points.push(points[target]);
+                }
+                let route = graphics::Mesh::new_line(ctx, &points, 2.0, LINE_COLOR)?;
+                graphics::draw(ctx, &route, (na::Point2::new(0.0, 0.0),))?;
+            }
+        }
+
+        if let Ok(pos) = self.interpolation.try_pos_of(
+            &self.world.physics,
+            self.world.beach_ball.get_handle(),
+            alpha,
+        ) {
+            draw_physics_ball(
+                ctx,
+                self.world.beach_ball.get_radius(),
+                &pos,
+                self.world.beach_ball.get_color(),
+            )?;
+        }
+
+        if let Some(drag) = &self.drag {
+            let band = graphics::Rect::new(
+                drag.start.x.min(drag.end.x),
+                drag.start.y.min(drag.end.y),
+                (drag.end.x - drag.start.x).abs(),
+                (drag.end.y - drag.start.y).abs(),
+            );
+            if band.w > 0.0 && band.h > 0.0 {
+                let fill = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::fill(),
+                    band,
+                    BAND_COLOR,
+                )?;
+                graphics::draw(ctx, &fill, (na::Point2::new(0.0, 0.0),))?;
+                let outline = graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::stroke(1.0),
+                    band,
+                    BAND_OUTLINE_COLOR,
+                )?;
+                graphics::draw(ctx, &outline, (na::Point2::new(0.0, 0.0),))?;
+            }
+        }
 
-            if let Some(target) = floater.target {
-                let points = [Point2{x: pos[0], y: pos[1]}, Point2{x: target[0], y: target[1]}];
-                let line_to_target = graphics::Mesh::new_line(ctx, &points, 2.0, LINE_COLOR)?;
-                graphics::draw(ctx, &line_to_target, (na::Point2::new(0.0, 0.0),))?;
+        if let Some(drag) = &self.order_drag {
+            if !drag.herd && (drag.end - drag.start).norm() >= DRAG_THRESHOLD {
+                let points = [
+                    Point2 {
+                        x: drag.start.x,
+                        y: drag.start.y,
+                    },
+                    Point2 {
+                        x: drag.end.x,
+                        y: drag.end.y,
+                    },
+                ];
+                let facing = graphics::Mesh::new_line(ctx, &points, 2.0, BAND_OUTLINE_COLOR)?;
+                graphics::draw(ctx, &facing, (na::Point2::new(0.0, 0.0),))?;
             }
         }
 
-        draw_physics_ball(
+        Camera::reset_to_screen(ctx)?;
+        let mut label = self.time_control.label(self.world.tick);
+        label.push_str(" | formation: ");
+        label.push_str(self.formation.name());
+        if self.world.avoidance.is_some() {
+            label.push_str(" | avoidance");
+        }
+        for ai in self.world.ai.iter() {
+            label.push_str(&format!(
+                " | {} ai: {}",
+                ai.team().name(),
+                ai.difficulty().name()
+            ));
+        }
+        if self.rewind.is_rewound() {
+            label.push_str(" | rewound");
+        }
+        draw_text(ctx, &label, (10.0, 10.0), HUD_COLOR)?;
+        if let Some(soccer) = &self.world.soccer {
+            draw_text(ctx, &soccer.scoreboard(), (10.0, 30.0), HUD_COLOR)?;
+        }
+
+        let capacity = self.rewind.capacity() as f32;
+        let cursor = self
+            .rewind
+            .cursor()
+            .unwrap_or_else(|| self.rewind.len().saturating_sub(1));
+        draw_timeline(
             ctx,
-            BeachBall::size(),
-            &self.physics.get_pos_of(self.beach_ball.get_handle()),
-            BeachBall::color(),
+            timeline_rect(ctx),
+            self.rewind.len() as f32 / capacity,
+            Some((cursor + 1) as f32 / capacity),
         )?;
 
         graphics::present(ctx)
     }
 
-    fn mouse_button_down_event(&mut self, _ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
-        let clicked_pos = Vector2::new(x, y);
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        // Live input would make the replay diverge from the recording.
+        if self.replay.is_some() {
+            return;
+        }
+
+        if button == MouseButton::Left
+            && self.can_rewind()
+            && timeline_hit_rect(ctx).contains(Point2 { x, y })
+        {
+            self.scrubbing = true;
+            self.scrub_to(ctx, x);
+            return;
+        }
+
+        let pos = self.camera.screen_to_world(ctx, x, y);
         match button {
+            // Selection happens on release, once we know if it was a drag.
             MouseButton::Left => {
-                for floater in self.floaters.iter() {
-                    if self.physics.floater_contains(
-                        floater.get_handle(),
-                        Floater::size(),
-                        clicked_pos,
-                    ) {
-                        self.selected.insert(floater.get_handle());
-                    }
-                }
+                let mods = keyboard::active_mods(ctx);
+                self.drag = Some(Drag {
+                    start: pos,
+                    end: pos,
+                    mode: SelectMode::from_modifiers(
+                        mods.contains(KeyMods::SHIFT),
+                        mods.contains(KeyMods::CTRL),
+                    ),
+                });
             }
+            // Likewise orders, once we know which way to face.
             MouseButton::Right => {
-                for floater in self.floaters.iter_mut() {
-                    if self.selected.contains(&floater.get_handle()) {
-                        floater.set_target(clicked_pos);
-                    }
-                }
-                self.selected.clear();
+                let mods = keyboard::active_mods(ctx);
+                self.order_drag = Some(OrderDrag {
+                    start: pos,
+                    end: pos,
+                    queue: mods.contains(KeyMods::SHIFT),
+                    herd: mods.contains(KeyMods::CTRL),
+                });
             }
             _ => (),
         }
     }
+
+    fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if button == MouseButton::Right {
+            if let Some(drag) = self.order_drag.take() {
+                if drag.herd {
+                    self.record_and_apply(Input::Herd {
+                        x: drag.start.x,
+                        y: drag.start.y,
+                        queue: drag.queue,
+                    });
+                    return;
+                }
+                let end = self.camera.screen_to_world(ctx, x, y);
+                let facing = end - drag.start;
+                self.record_and_apply(Input::Command {
+                    x: drag.start.x,
+                    y: drag.start.y,
+                    keep_selection: true,
+                    queue: drag.queue,
+                    formation: Some(self.formation),
+                    facing: if facing.norm() < DRAG_THRESHOLD {
+                        None
+                    } else {
+                        Some((facing.x, facing.y))
+                    },
+                });
+            }
+            return;
+        }
+        if button != MouseButton::Left {
+            return;
+        }
+        self.scrubbing = false;
+
+        if let Some(drag) = self.drag.take() {
+            let end = self.camera.screen_to_world(ctx, x, y);
+            let input = if (end - drag.start).norm() < DRAG_THRESHOLD {
+                Input::Select {
+                    x: drag.start.x,
+                    y: drag.start.y,
+                    mode: drag.mode,
+                }
+            } else {
+                Input::SelectBox {
+                    x0: drag.start.x,
+                    y0: drag.start.y,
+                    x1: end.x,
+                    y1: end.y,
+                    mode: drag.mode,
+                }
+            };
+            self.record_and_apply(input);
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if self.scrubbing {
+            self.scrub_to(ctx, x);
+        }
+        if let Some(drag) = &mut self.drag {
+            drag.end = self.camera.screen_to_world(ctx, x, y);
+        }
+        if let Some(drag) = &mut self.order_drag {
+            drag.end = self.camera.screen_to_world(ctx, x, y);
+        }
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        keymods: KeyMods,
+        _repeat: bool,
+    ) {
+        if self.time_control.handle_key(keycode) {
+            return;
+        }
+
+        if let Some(group) = control_group(keycode) {
+            if self.replay.is_some() {
+                return;
+            }
+            if keymods.contains(KeyMods::CTRL) {
+                self.record_and_apply(Input::AssignGroup { group });
+            } else {
+                self.recall_group(ctx, group);
+            }
+            return;
+        }
+
+        let input = match keycode {
+            KeyCode::Z => Input::Assign {
+                controller: ControllerKind::Seek(Seek::default()),
+            },
+            KeyCode::X => Input::Assign {
+                controller: ControllerKind::default(),
+            },
+            KeyCode::C => Input::Assign {
+                controller: ControllerKind::Flee(Flee::default()),
+            },
+            KeyCode::V => Input::Assign {
+                controller: ControllerKind::Wander(WanderSettings {
+                    seed: self.world.tick,
+                    ..WanderSettings::default()
+                }),
+            },
+            KeyCode::B => Input::Assign {
+                controller: ControllerKind::Pursue(Pursue::default()),
+            },
+            KeyCode::G => Input::Assign {
+                controller: ControllerKind::Flock(Flock::default()),
+            },
+            // Patrol goes on unless every selected floater already has it.
+            KeyCode::L => Input::Patrol {
+                on: !self.world.floaters.iter().all(|floater| {
+                    !self.selection.contains(floater.get_handle()) || floater.orders.patrol
+                }),
+            },
+            KeyCode::H => Input::Hold,
+            KeyCode::F => {
+                self.formation = self.formation.next();
+                return;
+            }
+            KeyCode::O => {
+                let cursor = mouse::position(ctx);
+                let point = self.camera.screen_to_world(ctx, cursor.x, cursor.y);
+                Input::Obstacle {
+                    x: point.x,
+                    y: point.y,
+                }
+            }
+            KeyCode::A => Input::Avoidance {
+                on: self.world.avoidance.is_none(),
+            },
+            KeyCode::N => {
+                self.show_navigation = !self.show_navigation;
+                return;
+            }
+            KeyCode::T => {
+                self.show_zones = !self.show_zones;
+                return;
+            }
+            KeyCode::Home => return self.camera.reset(),
+            KeyCode::Escape => return event::quit(ctx),
+            KeyCode::F5 => return self.quick_save(),
+            KeyCode::F9 => return self.quick_load(),
+            _ => return,
+        };
+        if self.replay.is_none() {
+            self.record_and_apply(input);
+        }
+    }
+}
+
+// Recordings made before selection modes always added to the selection.
+fn legacy_select_mode() -> SelectMode {
+    SelectMode::Add
+}
+
+fn control_group(keycode: KeyCode) -> Option<usize> {
+    let group = match keycode {
+        KeyCode::Key0 => 0,
+        KeyCode::Key1 => 1,
+        KeyCode::Key2 => 2,
+        KeyCode::Key3 => 3,
+        KeyCode::Key4 => 4,
+        KeyCode::Key5 => 5,
+        KeyCode::Key6 => 6,
+        KeyCode::Key7 => 7,
+        KeyCode::Key8 => 8,
+        KeyCode::Key9 => 9,
+        _ => return None,
+    };
+    Some(group)
+}
+
+fn saved_orders(world: &TopDownWorld, selection: &Selection) -> SavedOrders {
+    SavedOrders {
+        floaters: world
+            .floaters
+            .iter()
+            .map(|floater| {
+                (
+                    floater.target,
+                    floater.orders.clone(),
+                    floater.controller.kind(),
+                )
+            })
+            .collect(),
+        formations: world.formations.clone(),
+        soccer: world.soccer.clone(),
+        avoidance: world.avoidance,
+        selection: selection.clone(),
+        zones: world
+            .zones
+            .iter()
+            .map(|zone| zone.inside().copied().collect())
+            .collect(),
+    }
+}
+
+fn restore_frame(world: &mut TopDownWorld, selection: &mut Selection, frame: &Frame<SavedOrders>) {
+    frame.restore(&mut world.physics);
+    world.formations = frame.extra.formations.clone();
+    world.soccer = frame.extra.soccer.clone();
+    world.avoidance = frame.extra.avoidance;
+    *selection = frame.extra.selection.clone();
+    for (zone, inside) in frame.extra.zones.iter().enumerate() {
+        world.zones.set_inside(zone, inside.clone());
+    }
+    for ai in world.ai.iter_mut() {
+        ai.reset();
+    }
+    for (index, (floater, saved)) in world
+        .floaters
+        .iter_mut()
+        .zip(frame.extra.floaters.iter())
+        .enumerate()
+    {
+        floater.target = saved.0;
+        floater.orders = saved.1.clone();
+        if floater.controller.kind() == saved.2 {
+            floater.controller.reset();
+        } else {
+            floater.set_controller(saved.2.build(index as u64));
+        }
+        floater.path.clear();
+    }
+    world.tick = frame.tick;
 }
 
-fn draw_physics_ball(
-    ctx: &mut Context,
-    size: f32,
-    translation: &Vector2<f32>,
-    color: graphics::Color,
-) -> GameResult<()> {
-    let circle = graphics::Mesh::new_circle(
-        ctx,
-        graphics::DrawMode::fill(),
-        na::Point2::new(translation[0], translation[1]),
-        size,
-        TOLERANCE,
-        color,
-    )?;
-
-    graphics::draw(ctx, &circle, (na::Point2::new(0.0, 0.0),))
+/// The rewind timeline along the bottom of the window.
+fn timeline_rect(ctx: &Context) -> graphics::Rect {
+    let (width, height) = graphics::drawable_size(ctx);
+    graphics::Rect::new(10.0, height - 20.0, width - 20.0, 8.0)
+}
+
+/// The timeline with some slack around it, so it's easy to grab.
+fn timeline_hit_rect(ctx: &Context) -> graphics::Rect {
+    let bar = timeline_rect(ctx);
+    graphics::Rect::new(bar.x, bar.y - 6.0, bar.w, bar.h + 12.0)
 }
 
 fn main() {
+    let options = match RunOptions::from_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\nusage: top_down {}", e, RUN_USAGE);
+            return;
+        }
+    };
+
+    // A replay brings its own scene and seed, otherwise load the scene,
+    // falling back to the built-in one.
+    let replay_log = match &options.replay {
+        Some(path) => match snapshot::load::<InputLog<Input>, _>(path) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                println!("Error loading {}: {}", path, e);
+                return;
+            }
+        },
+        None => None,
+    };
+    let (scene, seed, rate) = match &replay_log {
+        Some(log) => (log.scene.clone(), log.seed, log.rate),
+        None => match options.load_scene(Scene::top_down) {
+            Ok(scene) => (
+                scene,
+                options.seed.unwrap_or_else(rand::random),
+                options.rate,
+            ),
+            Err(e) => {
+                println!("Error loading scene: {}", e);
+                return;
+            }
+        },
+    };
+    println!("Seed: {}", seed);
+
+    let world = match TopDownWorld::from_scene(&scene, &mut StdRng::seed_from_u64(seed)) {
+        Ok(world) => world,
+        Err(e) => {
+            println!("Error building world: {}", e);
+            return;
+        }
+    };
+
     // Make a Context.
     let (mut ctx, mut event_loop) = ContextBuilder::new("my_game", "Cool Game Author")
+        .window_mode(WindowMode::default().dimensions(scene.arena.width, scene.arena.height))
         .build()
         .expect("aieee, could not create ggez context!");
 
     // Create an instance of your event handler.
     // Usually, you should provide it with the Context object to
     // use when setting your game up.
-    let mut my_game = MyGame::new(&mut ctx);
+    let timestep = FixedTimestep::new(rate, options.substep_cap());
+    let mut my_game = MyGame::new(&mut ctx, world, timestep);
+    my_game.replay = replay_log.map(Replay::new);
+    my_game.recording = options
+        .record
+        .map(|path| (path, InputLog::new(seed, scene, rate)));
 
     // Run!
     match event::run(&mut ctx, &mut event_loop, &mut my_game) {
         Ok(_) => println!("Exited cleanly."),
         Err(e) => println!("Error occured: {}", e),
     }
+
+    my_game.save_recording();
 }
@@ -0,0 +1,176 @@
+use rust_physics_workbench::tournament::{self, Standing, Strategy};
+use rust_physics_workbench::Scene;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+
+const USAGE: &str = "usage: tournament [--scene FILE] [--strategies NAME,NAME,...] [--rounds N] \
+                     [--seed N] [--threads N] [--duration SECONDS] [--format csv|markdown] [--out FILE]";
+
+// Structures & enums
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Csv,
+    Markdown,
+}
+
+struct Options {
+    scene: Option<String>,
+    strategies: Vec<Strategy>,
+    rounds: u32,
+    seed: u64,
+    threads: usize,
+    duration: Option<f32>,
+    format: Format,
+    out: Option<String>,
+}
+
+impl Options {
+    fn from_args(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            scene: None,
+            strategies: Strategy::registered(),
+            rounds: 1,
+            seed: 0,
+            threads: 4,
+            duration: None,
+            format: Format::Markdown,
+            out: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--scene" => options.scene = Some(value()?.clone()),
+                "--strategies" => options.strategies = parse_strategies(value()?)?,
+                "--rounds" => {
+                    options.rounds = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&rounds| rounds > 0)
+                        .ok_or_else(|| "--rounds expects a positive whole number".to_string())?;
+                }
+                "--seed" => {
+                    options.seed = value()?
+                        .parse()
+                        .map_err(|_| "--seed expects a whole number".to_string())?;
+                }
+                "--threads" => {
+                    options.threads = value()?
+                        .parse()
+                        .ok()
+                        .filter(|&threads| threads > 0)
+                        .ok_or_else(|| "--threads expects a positive whole number".to_string())?;
+                }
+                "--duration" => {
+                    options.duration = Some(
+                        value()?
+                            .parse()
+                            .ok()
+                            .filter(|&duration: &f32| duration > 0.0)
+                            .ok_or_else(|| "--duration expects a positive number".to_string())?,
+                    );
+                }
+                "--format" => {
+                    options.format = match value()?.as_str() {
+                        "csv" => Format::Csv,
+                        "markdown" | "md" => Format::Markdown,
+                        other => return Err(format!("unknown format {}", other)),
+                    };
+                }
+                "--out" => options.out = Some(value()?.clone()),
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+
+        if options.strategies.len() < 2 {
+            return Err("a tournament needs at least two strategies".to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn parse_strategies(text: &str) -> Result<Vec<Strategy>, String> {
+    text.split(',')
+        .map(|name| {
+            let name = name.trim();
+            Strategy::find(name).ok_or_else(|| {
+                let known: Vec<String> = Strategy::registered()
+                    .into_iter()
+                    .map(|strategy| strategy.name)
+                    .collect();
+                format!(
+                    "unknown strategy {}, expected one of {}",
+                    name,
+                    known.join(", ")
+                )
+            })
+        })
+        .collect()
+}
+
+fn write_standings<W: Write>(
+    format: Format,
+    standings: &[Standing],
+    out: &mut W,
+) -> io::Result<()> {
+    match format {
+        Format::Csv => tournament::write_csv(standings, out),
+        Format::Markdown => tournament::write_markdown(standings, out),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::from_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+
+    let mut scene = match options.scene {
+        Some(path) => match Scene::from_file(&path) {
+            Ok(scene) => scene,
+            Err(e) => {
+                eprintln!("Error loading {}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => Scene::soccer(),
+    };
+    if let (Some(soccer), Some(duration)) = (scene.soccer.as_mut(), options.duration) {
+        soccer.duration = duration;
+    }
+
+    let fixtures = tournament::round_robin(options.strategies.len(), options.rounds, options.seed);
+    let results = match tournament::run(&scene, &options.strategies, &fixtures, options.threads) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("Error playing the tournament: {}", e);
+            process::exit(1);
+        }
+    };
+    let standings = tournament::leaderboard(&options.strategies, &results);
+
+    let format = options.format;
+    let result = match options.out {
+        Some(path) => {
+            File::create(path).and_then(|mut file| write_standings(format, &standings, &mut file))
+        }
+        None => write_standings(format, &standings, &mut io::stdout()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error writing the leaderboard: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,54 @@
+use ggez::{graphics, Context, GameResult};
+use nalgebra::Vector2;
+
+// Structures & enums
+
+/// Which part of the world the window shows. World drawing happens between
+/// `apply` and `reset_to_screen`; anything drawn after that, like the HUD,
+/// stays put on screen.
+pub struct Camera {
+    center: Vector2<f32>,
+    home: Vector2<f32>,
+}
+
+impl Camera {
+    pub fn new(home: Vector2<f32>) -> Self {
+        Camera { center: home, home }
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        self.center
+    }
+
+    pub fn center_on(&mut self, point: Vector2<f32>) {
+        self.center = point;
+    }
+
+    /// Goes back to where the camera started.
+    pub fn reset(&mut self) {
+        self.center = self.home;
+    }
+
+    pub fn screen_to_world(&self, ctx: &Context, x: f32, y: f32) -> Vector2<f32> {
+        let (width, height) = graphics::drawable_size(ctx);
+        self.center + Vector2::new(x - width / 2.0, y - height / 2.0)
+    }
+
+    pub fn apply(&self, ctx: &mut Context) -> GameResult<()> {
+        let (width, height) = graphics::drawable_size(ctx);
+        graphics::set_screen_coordinates(
+            ctx,
+            graphics::Rect::new(
+                self.center.x - width / 2.0,
+                self.center.y - height / 2.0,
+                width,
+                height,
+            ),
+        )
+    }
+
+    pub fn reset_to_screen(ctx: &mut Context) -> GameResult<()> {
+        let (width, height) = graphics::drawable_size(ctx);
+        graphics::set_screen_coordinates(ctx, graphics::Rect::new(0.0, 0.0, width, height))
+    }
+}
@@ -0,0 +1,130 @@
+use crate::scene::{Scene, SceneError};
+use crate::timestep::{DEFAULT_MAX_SUBSTEPS, DEFAULT_RATE};
+
+// Structures & enums
+
+/// Command line options shared by the binaries. Ones with options of their
+/// own read the shared ones through `parse_arg`.
+#[derive(Debug)]
+pub struct RunOptions {
+    pub scene: Option<String>,
+    pub seed: Option<u64>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub rate: u32,
+    /// Most physics steps to catch up on per frame, when given.
+    pub max_substeps: Option<u32>,
+}
+
+pub const RUN_USAGE: &str = "[SCENE | --scene FILE] [--seed N] [--record FILE | --replay FILE] \
+                             [--rate HZ] [--max-substeps N]";
+
+impl RunOptions {
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Result<RunOptions, String> {
+        let mut options = RunOptions::default();
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            if !options.parse_arg(&arg, &mut args)? {
+                return Err(format!("unknown argument {}", arg));
+            }
+        }
+        options.check()?;
+        Ok(options)
+    }
+
+    /// Takes `arg` if it's one of the shared options, reading its value from
+    /// `args`. Returns whether it was, so binaries with options of their own
+    /// can go on to parse it themselves.
+    pub fn parse_arg<I: Iterator<Item = String>>(
+        &mut self,
+        arg: &str,
+        args: &mut I,
+    ) -> Result<bool, String> {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", arg))
+        };
+        match arg {
+            "--scene" => self.scene = Some(value()?),
+            "--seed" => {
+                self.seed = Some(
+                    value()?
+                        .parse()
+                        .map_err(|_| "--seed expects a whole number".to_string())?,
+                );
+            }
+            "--rate" => {
+                self.rate = value()?
+                    .parse()
+                    .ok()
+                    .filter(|&rate| rate > 0)
+                    .ok_or_else(|| "--rate expects a positive whole number".to_string())?;
+            }
+            "--max-substeps" => {
+                self.max_substeps = Some(
+                    value()?
+                        .parse()
+                        .ok()
+                        .filter(|&steps| steps > 0)
+                        .ok_or_else(|| {
+                            "--max-substeps expects a positive whole number".to_string()
+                        })?,
+                );
+            }
+            "--record" => self.record = Some(value()?),
+            "--replay" => self.replay = Some(value()?),
+            _ if !arg.starts_with("--") && self.scene.is_none() => {
+                self.scene = Some(arg.to_string())
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Checks the options against each other, once every argument is in.
+    pub fn check(&self) -> Result<(), String> {
+        if self.record.is_some() && self.replay.is_some() {
+            return Err("--record and --replay can't be used together".to_string());
+        }
+        Ok(())
+    }
+
+    /// Like `check`, for runs without a window: there are no frames to
+    /// catch up on and no inputs to record or replay.
+    pub fn check_headless(&self) -> Result<(), String> {
+        self.check()?;
+        if self.record.is_some() || self.replay.is_some() {
+            return Err("headless runs can't record or replay".to_string());
+        }
+        if self.max_substeps.is_some() {
+            return Err("--max-substeps only applies to windowed runs".to_string());
+        }
+        Ok(())
+    }
+
+    /// Most physics steps to run in one frame.
+    pub fn substep_cap(&self) -> u32 {
+        self.max_substeps.unwrap_or(DEFAULT_MAX_SUBSTEPS)
+    }
+
+    /// Loads the scene named on the command line, or `default` if there wasn't one.
+    pub fn load_scene(&self, default: fn() -> Scene) -> Result<Scene, SceneError> {
+        match &self.scene {
+            Some(path) => Scene::from_file(path),
+            None => Ok(default()),
+        }
+    }
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            scene: None,
+            seed: None,
+            record: None,
+            replay: None,
+            rate: DEFAULT_RATE,
+            max_substeps: None,
+        }
+    }
+}
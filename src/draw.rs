@@ -0,0 +1,224 @@
+use crate::navigation::NavGrid;
+use crate::physics::PhysicsStruct;
+use crate::scene::{SceneBody, ShapeDesc};
+use crate::soccer::Goal;
+use crate::zones::{Zone, ZoneShape};
+
+use ggez::nalgebra as na;
+use ggez::{graphics, Context, GameResult};
+use nalgebra::Vector2;
+use ncollide2d::shape::ConvexPolygon;
+
+// Constants
+const TOLERANCE: f32 = 0.001;
+const GOAL_ALPHA: f32 = 0.3;
+const PROP_COLOR: graphics::Color = graphics::Color::new(0.4, 0.4, 0.4, 1.0);
+const TIMELINE_BACKGROUND: graphics::Color = graphics::Color::new(0.85, 0.85, 0.85, 0.8);
+const TIMELINE_COLOR: graphics::Color = graphics::Color::new(0.3, 0.5, 0.9, 0.8);
+const NAV_BLOCKED_COLOR: graphics::Color = graphics::Color::new(0.9, 0.4, 0.1, 0.25);
+const ZONE_COLOR: graphics::Color = graphics::Color::new(0.6, 0.2, 0.8, 1.0);
+const ZONE_ALPHA: f32 = 0.5;
+pub const HUD_COLOR: graphics::Color = graphics::Color::new(0.1, 0.1, 0.1, 1.0);
+
+pub fn draw_physics_ball(
+    ctx: &mut Context,
+    size: f32,
+    translation: &Vector2<f32>,
+    color: graphics::Color,
+) -> GameResult<()> {
+    let circle = graphics::Mesh::new_circle(
+        ctx,
+        graphics::DrawMode::fill(),
+        na::Point2::new(translation[0], translation[1]),
+        size,
+        TOLERANCE,
+        color,
+    )?;
+
+    graphics::draw(ctx, &circle, (na::Point2::new(0.0, 0.0),))
+}
+
+/// An outline around a ball, for marking it without changing its color.
+pub fn draw_ring(
+    ctx: &mut Context,
+    size: f32,
+    translation: &Vector2<f32>,
+    color: graphics::Color,
+) -> GameResult<()> {
+    let circle = graphics::Mesh::new_circle(
+        ctx,
+        graphics::DrawMode::stroke(2.0),
+        na::Point2::new(translation[0], translation[1]),
+        size,
+        TOLERANCE,
+        color,
+    )?;
+
+    graphics::draw(ctx, &circle, (na::Point2::new(0.0, 0.0),))
+}
+
+/// A goal mouth, shaded in the defending team's color.
+pub fn draw_goal(ctx: &mut Context, goal: &Goal) -> GameResult<()> {
+    let mut color = goal.defender.color();
+    color.a = GOAL_ALPHA;
+    let rect = graphics::Mesh::new_rectangle(
+        ctx,
+        graphics::DrawMode::fill(),
+        graphics::Rect::new(
+            goal.center.x - goal.half_extents.x,
+            goal.center.y - goal.half_extents.y,
+            goal.half_extents.x * 2.0,
+            goal.half_extents.y * 2.0,
+        ),
+        color,
+    )?;
+    graphics::draw(ctx, &rect, (na::Point2::new(0.0, 0.0),))
+}
+
+pub fn draw_prop(ctx: &mut Context, physics: &PhysicsStruct, prop: &SceneBody) -> GameResult<()> {
+    let iso = physics.get_collider_pos_of(prop.handle);
+    let pos = iso.translation.vector;
+    let color = prop.color.unwrap_or(PROP_COLOR);
+    match prop.shape {
+        ShapeDesc::Ball { radius } => draw_physics_ball(ctx, radius, &pos, color),
+        ShapeDesc::Cuboid {
+            half_width,
+            half_height,
+        } => {
+            let rect = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                graphics::Rect::new(
+                    -half_width,
+                    -half_height,
+                    half_width * 2.0,
+                    half_height * 2.0,
+                ),
+                color,
+            )?;
+            let param = graphics::DrawParam::new()
+                .dest(na::Point2::new(pos[0], pos[1]))
+                .rotation(iso.rotation.angle());
+            graphics::draw(ctx, &rect, param)
+        }
+    }
+}
+
+/// A trigger zone's outline and name. Its color is see-through whatever the
+/// scene asked for, so the zone never hides what's in it.
+pub fn draw_zone(ctx: &mut Context, zone: &Zone) -> GameResult<()> {
+    let mut color = zone.desc.color().unwrap_or(ZONE_COLOR);
+    color.a = color.a.min(ZONE_ALPHA);
+    let pos = zone.desc.position();
+    let mode = graphics::DrawMode::stroke(2.0);
+    let outline = match &zone.desc.shape {
+        ZoneShape::Ball { radius } => graphics::Mesh::new_circle(
+            ctx,
+            mode,
+            na::Point2::new(0.0, 0.0),
+            *radius,
+            TOLERANCE,
+            color,
+        )?,
+        ZoneShape::Cuboid {
+            half_width,
+            half_height,
+        } => graphics::Mesh::new_rectangle(
+            ctx,
+            mode,
+            graphics::Rect::new(
+                -half_width,
+                -half_height,
+                half_width * 2.0,
+                half_height * 2.0,
+            ),
+            color,
+        )?,
+        ZoneShape::Polygon { .. } => {
+            // The hull the sensor actually uses, not the points as written.
+            let shape = zone
+                .desc
+                .shape_handle()
+                .expect("Zones are validated when added");
+            let hull: Vec<na::Point2<f32>> = shape
+                .as_shape::<ConvexPolygon<f32>>()
+                .expect("Polygon zones have convex polygon shapes")
+                .points()
+                .iter()
+                .map(|point| na::Point2::new(point.x, point.y))
+                .collect();
+            graphics::Mesh::new_polygon(ctx, mode, &hull, color)?
+        }
+    };
+    graphics::draw(ctx, &outline, (na::Point2::new(pos.x, pos.y),))?;
+    draw_text(ctx, &zone.desc.name, (pos.x, pos.y), color)
+}
+
+/// Shades the cells floaters route around.
+pub fn draw_nav_grid(ctx: &mut Context, grid: &NavGrid) -> GameResult<()> {
+    let (columns, rows) = grid.dims();
+    let cell = grid.cell_size();
+    let mut builder = graphics::MeshBuilder::new();
+    let mut any = false;
+    for row in 0..rows {
+        for column in 0..columns {
+            if grid.is_blocked((column, row)) {
+                builder.rectangle(
+                    graphics::DrawMode::fill(),
+                    graphics::Rect::new(column as f32 * cell, row as f32 * cell, cell, cell),
+                    NAV_BLOCKED_COLOR,
+                );
+                any = true;
+            }
+        }
+    }
+    if !any {
+        return Ok(());
+    }
+    let mesh = builder.build(ctx)?;
+    graphics::draw(ctx, &mesh, (na::Point2::new(0.0, 0.0),))
+}
+
+pub fn draw_text(
+    ctx: &mut Context,
+    text: &str,
+    pos: (f32, f32),
+    color: graphics::Color,
+) -> GameResult<()> {
+    let text = graphics::Text::new(text);
+    graphics::draw(ctx, &text, (na::Point2::new(pos.0, pos.1), color))
+}
+
+/// A bar across `bar` filled up to `filled` (0 to 1) with a marker at
+/// `cursor`, if there is one.
+pub fn draw_timeline(
+    ctx: &mut Context,
+    bar: graphics::Rect,
+    filled: f32,
+    cursor: Option<f32>,
+) -> GameResult<()> {
+    let background =
+        graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), bar, TIMELINE_BACKGROUND)?;
+    graphics::draw(ctx, &background, (na::Point2::new(0.0, 0.0),))?;
+
+    if filled > 0.0 {
+        let mut extent = bar;
+        extent.w = bar.w * filled.min(1.0);
+        let extent =
+            graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), extent, TIMELINE_COLOR)?;
+        graphics::draw(ctx, &extent, (na::Point2::new(0.0, 0.0),))?;
+    }
+
+    if let Some(cursor) = cursor {
+        let x = (bar.x + bar.w * cursor).max(bar.x).min(bar.x + bar.w);
+        let marker = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(x - 2.0, bar.y - 4.0, 4.0, bar.h + 8.0),
+            HUD_COLOR,
+        )?;
+        graphics::draw(ctx, &marker, (na::Point2::new(0.0, 0.0),))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use nalgebra::Vector2;
+use nphysics2d::object::DefaultBodyHandle;
+
+// Structures & enums
+
+/// Two bodies touching, as seen when they first met.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub body1: DefaultBodyHandle,
+    pub body2: DefaultBodyHandle,
+    /// Where they touch, in world coordinates.
+    pub point: Vector2<f32>,
+    /// Unit normal pointing from `body1` toward `body2`.
+    pub normal: Vector2<f32>,
+    /// Roughly how hard they met: the momentum along the normal it would
+    /// take to stop them closing, from their velocities going into the step.
+    /// An estimate, not the solver's impulse, which nphysics keeps to itself;
+    /// it ignores restitution, friction and any other contacts in the step.
+    pub closing_impulse_estimate: f32,
+}
+
+/// Something that happened between two bodies during the last step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhysicsEvent {
+    ContactStarted(Contact),
+    ContactStopped {
+        body1: DefaultBodyHandle,
+        body2: DefaultBodyHandle,
+    },
+    /// `body` started overlapping the sensor body `sensor`.
+    SensorEntered {
+        sensor: DefaultBodyHandle,
+        body: DefaultBodyHandle,
+    },
+    SensorExited {
+        sensor: DefaultBodyHandle,
+        body: DefaultBodyHandle,
+    },
+}
+
+impl Contact {
+    /// The same contact seen from `body2`'s side.
+    pub fn flipped(&self) -> Contact {
+        Contact {
+            body1: self.body2,
+            body2: self.body1,
+            point: self.point,
+            normal: -self.normal,
+            closing_impulse_estimate: self.closing_impulse_estimate,
+        }
+    }
+
+    /// The contact with `body` as `body1`, if it's one of the two.
+    pub fn seen_from(&self, body: DefaultBodyHandle) -> Option<Contact> {
+        if self.body1 == body {
+            Some(*self)
+        } else if self.body2 == body {
+            Some(self.flipped())
+        } else {
+            None
+        }
+    }
+}
+
+impl PhysicsEvent {
+    /// The two bodies involved. For sensor events the sensor comes first.
+    pub fn bodies(&self) -> (DefaultBodyHandle, DefaultBodyHandle) {
+        match *self {
+            PhysicsEvent::ContactStarted(contact) => (contact.body1, contact.body2),
+            PhysicsEvent::ContactStopped { body1, body2 } => (body1, body2),
+            PhysicsEvent::SensorEntered { sensor, body }
+            | PhysicsEvent::SensorExited { sensor, body } => (sensor, body),
+        }
+    }
+
+    pub fn involves(&self, body: DefaultBodyHandle) -> bool {
+        let (first, second) = self.bodies();
+        first == body || second == body
+    }
+
+    /// Whether this happened between `a` and `b`, either way round.
+    pub fn between(&self, a: DefaultBodyHandle, b: DefaultBodyHandle) -> bool {
+        let bodies = self.bodies();
+        bodies == (a, b) || bodies == (b, a)
+    }
+}
@@ -0,0 +1,203 @@
+use crate::arrival::ArrivalController;
+use crate::navigation::NavPath;
+use crate::soccer::Team;
+use crate::steering::FloaterController;
+
+use ggez::graphics::Color;
+use nalgebra::Vector2;
+use nphysics2d::object::DefaultBodyHandle;
+
+use std::collections::VecDeque;
+
+pub const BLUE: Color = Color {
+    r: 0.01,
+    g: 0.33,
+    b: 0.98,
+    a: 1.0,
+};
+
+/* -- Eventually we'll be able to choose a color -- */
+pub const GREEN: Color = Color {
+    r: 0.01,
+    g: 0.98,
+    b: 0.43,
+    a: 1.0,
+};
+
+pub const RED: Color = Color {
+    r: 0.98,
+    g: 0.01,
+    b: 0.35,
+    a: 1.0,
+};
+
+pub trait PhysicsBall {
+    fn get_handle(&self) -> DefaultBodyHandle;
+    fn get_radius(&self) -> f32;
+    fn get_color(&self) -> Color;
+    fn size() -> f32;
+    fn color() -> Color;
+}
+
+/// What a floater does after reaching its current target.
+#[derive(Clone, Debug, Default)]
+pub struct Orders {
+    /// Waypoints still to visit after the current target.
+    pub waypoints: VecDeque<Vector2<f32>>,
+    /// Loop through the waypoints instead of stopping after the last one.
+    pub patrol: bool,
+    /// Keep returning to the target rather than dropping it once reached.
+    pub hold: bool,
+}
+
+pub struct Floater {
+    handle: DefaultBodyHandle,
+    radius: f32,
+    color: Color,
+    pub target: Option<Vector2<f32>>,
+    pub orders: Orders,
+    pub controller: Box<dyn FloaterController>,
+    pub path: NavPath,
+    /// Which side it plays for in a soccer match.
+    pub team: Option<Team>,
+}
+
+impl Floater {
+    pub fn new(handle: DefaultBodyHandle) -> Self {
+        Floater::with_style(handle, Floater::size(), Floater::color())
+    }
+
+    pub fn with_style(handle: DefaultBodyHandle, radius: f32, color: Color) -> Self {
+        Floater {
+            handle,
+            radius,
+            color,
+            target: None,
+            orders: Orders::default(),
+            controller: Box::new(ArrivalController::default()),
+            path: NavPath::default(),
+            team: None,
+        }
+    }
+
+    /// Replaces every order with a move to `target`.
+    pub fn set_target(&mut self, target: Vector2<f32>) {
+        self.orders = Orders::default();
+        self.target = Some(target);
+        self.controller.reset();
+    }
+
+    /// Adds `waypoint` after the ones already queued. Cancels a hold.
+    pub fn queue_waypoint(&mut self, waypoint: Vector2<f32>) {
+        if self.target.is_none() || self.orders.hold {
+            let patrol = self.orders.patrol;
+            self.set_target(waypoint);
+            self.orders.patrol = patrol;
+        } else {
+            self.orders.waypoints.push_back(waypoint);
+        }
+    }
+
+    pub fn set_patrol(&mut self, patrol: bool) {
+        self.orders.patrol = patrol;
+    }
+
+    /// Drops every order and stays at `position`, coming back if pushed away.
+    pub fn hold_position(&mut self, position: Vector2<f32>) {
+        self.set_target(position);
+        self.orders.hold = true;
+    }
+
+    /// Moves on to the next waypoint once the current target is reached.
+    pub fn reached_target(&mut self) {
+        if self.orders.hold {
+            return;
+        }
+        if self.orders.patrol {
+            if let Some(target) = self.target {
+                self.orders.waypoints.push_back(target);
+            }
+        }
+        self.target = self.orders.waypoints.pop_front();
+        self.controller.reset();
+    }
+
+    /// The current target followed by every queued waypoint.
+    pub fn route(&self) -> impl Iterator<Item = &Vector2<f32>> {
+        self.target.iter().chain(self.orders.waypoints.iter())
+    }
+
+    pub fn set_controller(&mut self, controller: Box<dyn FloaterController>) {
+        self.controller = controller;
+    }
+
+    /// Puts the floater on `team`, in the team's colors.
+    pub fn set_team(&mut self, team: Team) {
+        self.team = Some(team);
+        self.color = team.color();
+    }
+}
+
+impl PhysicsBall for Floater {
+    fn get_handle(&self) -> DefaultBodyHandle {
+        self.handle
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn get_color(&self) -> Color {
+        self.color
+    }
+
+    fn size() -> f32 {
+        10.0
+    }
+
+    fn color() -> Color {
+        BLUE
+    }
+}
+
+pub struct BeachBall {
+    handle: DefaultBodyHandle,
+    radius: f32,
+    color: Color,
+}
+
+impl BeachBall {
+    pub fn new(handle: DefaultBodyHandle) -> Self {
+        BeachBall::with_style(handle, BeachBall::size(), BeachBall::color())
+    }
+
+    pub fn with_style(handle: DefaultBodyHandle, radius: f32, color: Color) -> Self {
+        BeachBall {
+            handle,
+            radius,
+            color,
+        }
+    }
+}
+
+impl PhysicsBall for BeachBall {
+    fn get_handle(&self) -> DefaultBodyHandle {
+        self.handle
+    }
+
+    fn get_radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn get_color(&self) -> Color {
+        self.color
+    }
+
+    fn size() -> f32 {
+        45.0
+    }
+
+    fn color() -> Color {
+        RED
+    }
+}
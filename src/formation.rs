@@ -0,0 +1,197 @@
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use std::f32::consts::PI;
+
+// Constants
+/// Gap left between neighbouring floaters in a formation.
+pub const FORMATION_GAP: f32 = 10.0;
+/// How fast a formation marches, slow enough for every floater to keep up.
+pub const FORMATION_SPEED: f32 = 100.0;
+
+// Structures & enums
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Formation {
+    Line,
+    Wedge,
+    Grid,
+    Circle,
+}
+
+/// A group marching in formation. The anchor walks from where the group
+/// started to `destination`, waiting whenever a member falls out of place, and
+/// each member's target is its slot around the anchor.
+#[derive(Clone, Debug)]
+pub struct FormationMove {
+    /// Floater indices and their slot offsets from the anchor.
+    pub members: Vec<(usize, Vector2<f32>)>,
+    pub anchor: Vector2<f32>,
+    pub destination: Vector2<f32>,
+    pub speed: f32,
+    /// How far a member may stray from its slot before the anchor waits.
+    pub slack: f32,
+}
+
+impl Formation {
+    pub fn next(self) -> Self {
+        match self {
+            Formation::Line => Formation::Wedge,
+            Formation::Wedge => Formation::Grid,
+            Formation::Grid => Formation::Circle,
+            Formation::Circle => Formation::Line,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Formation::Line => "line",
+            Formation::Wedge => "wedge",
+            Formation::Grid => "grid",
+            Formation::Circle => "circle",
+        }
+    }
+
+    /// Slot offsets for `count` members, centred on the origin, with the
+    /// formation facing along `facing` (a unit vector).
+    pub fn slots(self, count: usize, spacing: f32, facing: Vector2<f32>) -> Vec<Vector2<f32>> {
+        // Built with +x forward and +y to the right, then turned to face.
+        let local: Vec<Vector2<f32>> = (0..count)
+            .map(|i| match self {
+                Formation::Line => {
+                    Vector2::new(0.0, (i as f32 - (count - 1) as f32 / 2.0) * spacing)
+                }
+                Formation::Wedge => {
+                    let row = (i as f32 / 2.0).ceil();
+                    let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+                    Vector2::new(-row * spacing, side * row * spacing)
+                }
+                Formation::Grid => {
+                    let columns = (count as f32).sqrt().ceil() as usize;
+                    let row = (i / columns) as f32;
+                    let column = (i % columns) as f32;
+                    Vector2::new(
+                        -row * spacing,
+                        (column - (columns - 1) as f32 / 2.0) * spacing,
+                    )
+                }
+                Formation::Circle if count == 1 => Vector2::zeros(),
+                Formation::Circle => {
+                    let radius = (spacing * count as f32 / (2.0 * PI)).max(spacing);
+                    let angle = 2.0 * PI * i as f32 / count as f32;
+                    Vector2::new(angle.cos(), angle.sin()) * radius
+                }
+            })
+            .collect();
+
+        let center =
+            local.iter().fold(Vector2::zeros(), |sum, slot| sum + slot) / count.max(1) as f32;
+        let right = Vector2::new(-facing.y, facing.x);
+        local
+            .iter()
+            .map(|slot| {
+                let slot = slot - center;
+                facing * slot.x + right * slot.y
+            })
+            .collect()
+    }
+}
+
+/// Pairs each position with a slot, closest pairs first, so members don't
+/// cross paths more than they need to. Returns the slot index for each
+/// position.
+pub fn assign_slots(positions: &[Vector2<f32>], slots: &[Vector2<f32>]) -> Vec<usize> {
+    let mut pairs = Vec::with_capacity(positions.len() * slots.len());
+    for (member, position) in positions.iter().enumerate() {
+        for (slot, offset) in slots.iter().enumerate() {
+            pairs.push(((position - offset).norm_squared(), member, slot));
+        }
+    }
+    pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut assigned = vec![None; positions.len()];
+    let mut taken = vec![false; slots.len()];
+    for (_, member, slot) in pairs {
+        if assigned[member].is_none() && !taken[slot] {
+            assigned[member] = Some(slot);
+            taken[slot] = true;
+        }
+    }
+    assigned
+        .into_iter()
+        .map(|slot| slot.expect("Every member gets a slot"))
+        .collect()
+}
+
+impl FormationMove {
+    /// Moves the anchor along if every member is close enough to its slot,
+    /// returning each member's target. `positions` gives floater positions
+    /// by index.
+    pub fn advance(&mut self, positions: &[Vector2<f32>], dt: f32) -> Vec<(usize, Vector2<f32>)> {
+        let in_place = self.members.iter().all(|&(index, offset)| {
+            (positions[index] - (self.anchor + offset)).norm() <= self.slack
+        });
+        if in_place {
+            let remaining = self.destination - self.anchor;
+            let step = self.speed * dt;
+            if remaining.norm() <= step {
+                self.anchor = self.destination;
+            } else {
+                self.anchor += remaining.normalize() * step;
+            }
+        }
+
+        self.members
+            .iter()
+            .map(|&(index, offset)| (index, self.anchor + offset))
+            .collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.anchor == self.destination || self.members.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_take_their_nearest_slots() {
+        let positions = [Vector2::new(10.0, 0.0), Vector2::new(-10.0, 0.0)];
+        let slots = [Vector2::new(-9.0, 0.0), Vector2::new(9.0, 0.0)];
+        assert_eq!(assign_slots(&positions, &slots), vec![1, 0]);
+    }
+
+    #[test]
+    fn every_member_gets_its_own_slot() {
+        // All three start nearest the same slot.
+        let positions = [
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(2.0, 0.0),
+        ];
+        let slots = Formation::Line.slots(3, 20.0, Vector2::new(1.0, 0.0));
+        let mut assigned = assign_slots(&positions, &slots);
+        assigned.sort_unstable();
+        assert_eq!(assigned, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn closest_pair_is_settled_first() {
+        // The first member is closer to slot 0, but the second sits on it.
+        let positions = [Vector2::new(5.0, 0.0), Vector2::new(0.0, 0.0)];
+        let slots = [Vector2::new(0.0, 0.0), Vector2::new(20.0, 0.0)];
+        assert_eq!(assign_slots(&positions, &slots), vec![1, 0]);
+    }
+
+    #[test]
+    fn wedge_rows_pair_up_behind_the_point() {
+        let slots = Formation::Wedge.slots(5, 10.0, Vector2::new(1.0, 0.0));
+        // Members 1 and 2 share a row, as do 3 and 4.
+        assert!((slots[1].x - slots[2].x).abs() < 1e-4);
+        assert!((slots[3].x - slots[4].x).abs() < 1e-4);
+        assert!((slots[0].x - slots[1].x - 10.0).abs() < 1e-4);
+        assert!((slots[1].x - slots[3].x - 10.0).abs() < 1e-4);
+    }
+}
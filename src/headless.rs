@@ -0,0 +1,85 @@
+use crate::floater::PhysicsBall;
+use crate::world::TopDownWorld;
+
+use nalgebra::Vector2;
+
+use std::fmt;
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BodyKind {
+    Floater,
+    BeachBall,
+}
+
+impl fmt::Display for BodyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BodyKind::Floater => write!(f, "floater"),
+            BodyKind::BeachBall => write!(f, "beach_ball"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BodyState {
+    pub kind: BodyKind,
+    pub index: usize,
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+}
+
+/// Advances `world` by `ticks` updates without opening a window.
+pub fn run(world: &mut TopDownWorld, ticks: u32) {
+    for _ in 0..ticks {
+        world.update();
+    }
+}
+
+/// Where every floater and the beach ball are and how fast they're going.
+/// Bodies that have been removed from the physics world are left out.
+pub fn body_states(world: &TopDownWorld) -> Vec<BodyState> {
+    let bodies = world
+        .floaters
+        .iter()
+        .enumerate()
+        .map(|(index, floater)| (BodyKind::Floater, index, floater.get_handle()))
+        .chain(std::iter::once((
+            BodyKind::BeachBall,
+            0,
+            world.beach_ball.get_handle(),
+        )));
+
+    let mut states = Vec::new();
+    for (kind, index, handle) in bodies {
+        if let (Ok(position), Ok(velocity)) = (
+            world.physics.try_get_pos_of(handle),
+            world.physics.try_get_vel_of(handle),
+        ) {
+            states.push(BodyState {
+                kind,
+                index,
+                position,
+                velocity,
+            });
+        }
+    }
+    states
+}
+
+pub fn write_csv<W: Write>(states: &[BodyState], out: &mut W) -> io::Result<()> {
+    writeln!(out, "kind,index,x,y,vx,vy")?;
+    for state in states {
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            state.kind,
+            state.index,
+            state.position[0],
+            state.position[1],
+            state.velocity[0],
+            state.velocity[1]
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use crate::steering::{ControllerKind, FloaterController, FloaterView, Steering, WorldView};
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+// Constants
+/// Widest a herd spreads around the back of the ball, either side of
+/// straight behind it, in radians.
+const MAX_SLOT_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+
+// Structures & enums
+
+/// Herds the beach ball to the floater's target: gets into a slot behind the
+/// ball, going around it rather than through it, then pushes it along.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HerdSettings {
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub responsiveness: f32,
+    /// Top speed while pushing. Pushers slow down as the ball nears the goal.
+    pub push_speed: f32,
+    /// How close the ball has to get to the goal.
+    pub goal_radius: f32,
+    /// Room kept from the ball when going around it, and how far off its
+    /// slot a floater can be and still push.
+    pub margin: f32,
+    /// Where behind the ball this floater pushes from, as an angle off
+    /// straight behind, so a herd doesn't all aim for the same spot.
+    pub slot: f32,
+}
+
+pub struct Herd {
+    settings: HerdSettings,
+    pushing: bool,
+}
+
+impl Default for HerdSettings {
+    fn default() -> Self {
+        HerdSettings {
+            max_speed: 200.0,
+            max_force: 1000.0,
+            responsiveness: 5.0,
+            push_speed: 80.0,
+            goal_radius: 20.0,
+            margin: 8.0,
+            slot: 0.0,
+        }
+    }
+}
+
+impl HerdSettings {
+    /// Spreads `count` pushers of `radius` around the back of a ball of
+    /// `ball_radius`, returning the slot for each.
+    pub fn slots(count: usize, radius: f32, ball_radius: f32) -> Vec<f32> {
+        let step = (radius * 2.0) / (ball_radius + radius);
+        let half = (count.max(1) - 1) as f32 / 2.0;
+        let step = if half > 0.0 {
+            step.min(MAX_SLOT_ANGLE / half)
+        } else {
+            step
+        };
+        (0..count).map(|i| (i as f32 - half) * step).collect()
+    }
+}
+
+impl Herd {
+    pub fn new(settings: HerdSettings) -> Self {
+        Herd {
+            settings,
+            pushing: false,
+        }
+    }
+}
+
+impl FloaterController for Herd {
+    fn compute_force(&mut self, floater: &FloaterView, world: &WorldView) -> Steering {
+        let settings = self.settings;
+        let goal = match floater.target {
+            Some(goal) => goal,
+            None => return Steering::Idle,
+        };
+        let ball = match world.physics.try_get_pos_of(world.beach_ball) {
+            Ok(ball) => ball,
+            Err(_) => return Steering::Idle,
+        };
+        let to_goal = goal - ball;
+        if to_goal.norm() <= settings.goal_radius {
+            self.pushing = false;
+            return Steering::Arrived;
+        }
+
+        let heading = to_goal.normalize();
+        let (sin, cos) = settings.slot.sin_cos();
+        let from_slot = Vector2::new(
+            heading.x * cos - heading.y * sin,
+            heading.x * sin + heading.y * cos,
+        );
+        let contact = world.ball_radius + floater.radius;
+        let slot = ball - from_slot * contact;
+        let off_slot = (slot - floater.position).norm();
+
+        // Some slack once pushing, so a wobble doesn't send us round again.
+        let tolerance = if self.pushing {
+            settings.margin * 3.0
+        } else {
+            settings.margin
+        };
+        self.pushing = off_slot <= tolerance;
+
+        let desired = if self.pushing {
+            let speed = settings.push_speed.min(to_goal.norm());
+            heading * speed + (slot - floater.position) * settings.responsiveness
+        } else {
+            let clearance = contact + settings.margin;
+            let waypoint = if segment_distance(floater.position, slot, ball)
+                < contact - settings.margin / 2.0
+            {
+                // Go around the side we're already on, aiming behind the ball.
+                let offset = floater.position - ball;
+                let side = if heading.perp(&offset) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                let beside = Vector2::new(-heading.y, heading.x) * side;
+                ball + (beside - heading).normalize() * clearance
+            } else {
+                slot
+            };
+            let offset = waypoint - floater.position;
+            // Ease in so we don't knock the ball on arrival.
+            let speed = settings
+                .max_speed
+                .min(offset.norm() * settings.responsiveness);
+            if offset.norm() > 0.0 {
+                offset.normalize() * speed
+            } else {
+                Vector2::zeros()
+            }
+        };
+
+        let force = (desired - floater.velocity) * settings.responsiveness;
+        Steering::Force(if force.norm() > settings.max_force {
+            force.normalize() * settings.max_force
+        } else {
+            force
+        })
+    }
+
+    fn kind(&self) -> ControllerKind {
+        ControllerKind::Herd(self.settings)
+    }
+
+    fn reset(&mut self) {
+        self.pushing = false;
+    }
+
+    /// The target is where the ball goes, not the floater.
+    fn follows_paths(&self) -> bool {
+        false
+    }
+}
+
+/// How close the segment from `from` to `to` passes to `point`.
+fn segment_distance(from: Vector2<f32>, to: Vector2<f32>, point: Vector2<f32>) -> f32 {
+    let along = to - from;
+    let length = along.norm_squared();
+    let t = if length > 0.0 {
+        (point - from).dot(&along).max(0.0).min(length) / length
+    } else {
+        0.0
+    };
+    (from + along * t - point).norm()
+}
@@ -0,0 +1,32 @@
+pub mod arrival;
+pub mod avoidance;
+pub mod camera;
+pub mod cli;
+pub mod draw;
+pub mod events;
+pub mod floater;
+pub mod formation;
+pub mod headless;
+pub mod herding;
+pub mod navigation;
+pub mod physics;
+pub mod replay;
+pub mod rewind;
+pub mod scene;
+pub mod selection;
+pub mod snapshot;
+pub mod soccer;
+pub mod spatial;
+pub mod steering;
+pub mod team_ai;
+pub mod time_control;
+pub mod timestep;
+pub mod tournament;
+pub mod world;
+pub mod zones;
+
+pub use floater::{BeachBall, Floater, Orders, PhysicsBall, BLUE, GREEN, RED};
+pub use physics::{PhysicsError, PhysicsStruct};
+pub use scene::{Scene, SceneError};
+pub use snapshot::SnapshotError;
+pub use world::TopDownWorld;
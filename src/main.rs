@@ -1,5 +0,0 @@
-// Moved gravity example to bin/side_view.rs
-
-fn main() {
-    println!("Hello, physics!");
-}
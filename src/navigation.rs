@@ -0,0 +1,369 @@
+use crate::physics::PhysicsStruct;
+
+use nalgebra::{Point2, Vector2};
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+// Constants
+/// Side of a navigation grid cell, in pixels.
+pub const NAV_CELL: f32 = 20.0;
+// A* step costs, scaled so diagonals stay integers.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// Structures & enums
+
+/// Which cells of the arena a floater can stand in without touching a static
+/// collider. Built from every `Ground` body's colliders, so it covers the
+/// walls, fixed props and obstacles added while running.
+pub struct NavGrid {
+    cell: f32,
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+    revision: u64,
+}
+
+/// The detour a floater is taking around obstacles to reach its target.
+#[derive(Clone, Debug, Default)]
+pub struct NavPath {
+    /// Corners still to pass before heading straight for the goal.
+    waypoints: VecDeque<Vector2<f32>>,
+    goal: Option<Vector2<f32>>,
+    revision: u64,
+}
+
+impl NavGrid {
+    /// Marks every cell whose centre is within `clearance` of a static
+    /// collider as blocked.
+    pub fn build(physics: &PhysicsStruct, cell: f32, clearance: f32) -> Self {
+        let (width, height) = physics.dims();
+        let columns = (width / cell).ceil().max(1.0) as usize;
+        let rows = (height / cell).ceil().max(1.0) as usize;
+        let mut grid = NavGrid {
+            cell,
+            columns,
+            rows,
+            blocked: vec![false; columns * rows],
+            revision: 0,
+        };
+
+        for (_, collider) in physics.colliders.iter() {
+            let is_static = matches!(
+                physics.bodies.get(collider.body()),
+                Some(body) if body.is_ground()
+            );
+            if !is_static || collider.is_sensor() {
+                continue;
+            }
+            let shape = collider.shape();
+            let position = collider.position();
+            let query = match shape.as_point_query() {
+                Some(query) => query,
+                None => continue,
+            };
+
+            // Only look at cells near the collider.
+            let aabb = shape.aabb(position);
+            let (min_column, min_row) =
+                grid.clamped_cell(aabb.mins().coords - Vector2::new(clearance, clearance));
+            let (max_column, max_row) =
+                grid.clamped_cell(aabb.maxs().coords + Vector2::new(clearance, clearance));
+            for row in min_row..=max_row {
+                for column in min_column..=max_column {
+                    let center = Point2::from(grid.center_of((column, row)));
+                    if query.distance_to_point(position, &center, true) <= clearance {
+                        grid.blocked[row * columns + column] = true;
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    /// Bumped whenever the grid is rebuilt, so paths planned on an older
+    /// grid know to plan again.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Replaces this grid with `rebuilt`, bumping the revision.
+    pub fn replace(&mut self, rebuilt: NavGrid) {
+        let revision = self.revision + 1;
+        *self = rebuilt;
+        self.revision = revision;
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell
+    }
+
+    pub fn dims(&self) -> (usize, usize) {
+        (self.columns, self.rows)
+    }
+
+    pub fn is_blocked(&self, cell: (usize, usize)) -> bool {
+        self.blocked[cell.1 * self.columns + cell.0]
+    }
+
+    pub fn cell_of(&self, point: Vector2<f32>) -> (usize, usize) {
+        self.clamped_cell(point)
+    }
+
+    pub fn center_of(&self, cell: (usize, usize)) -> Vector2<f32> {
+        Vector2::new(
+            (cell.0 as f32 + 0.5) * self.cell,
+            (cell.1 as f32 + 0.5) * self.cell,
+        )
+    }
+
+    /// Whether a floater could slide straight from `from` to `to` without
+    /// crossing a blocked cell.
+    pub fn line_of_sight(&self, from: Vector2<f32>, to: Vector2<f32>) -> bool {
+        let length = (to - from).norm();
+        let steps = (length / (self.cell / 2.0)).ceil() as usize;
+        (1..=steps).all(|step| {
+            let point = from + (to - from) * (step as f32 / steps as f32);
+            !self.is_blocked(self.cell_of(point))
+        })
+    }
+
+    /// Corners to pass on the way from `start` to `goal`, not counting `goal`
+    /// itself. Empty when the way is clear, `None` when there is no way.
+    /// Blocked ends are moved to the nearest free cell first, since floaters
+    /// often rest closer to a wall than the clearance allows.
+    pub fn find_path(&self, start: Vector2<f32>, goal: Vector2<f32>) -> Option<Vec<Vector2<f32>>> {
+        let start_cell = self.nearest_free(self.cell_of(start))?;
+        let goal_cell = self.nearest_free(self.cell_of(goal))?;
+        if self.line_of_sight(start, goal) {
+            return Some(Vec::new());
+        }
+
+        let index = |cell: (usize, usize)| cell.1 * self.columns + cell.0;
+        let heuristic = |cell: (usize, usize)| {
+            let dx = (cell.0 as i64 - goal_cell.0 as i64).abs();
+            let dy = (cell.1 as i64 - goal_cell.1 as i64).abs();
+            let straight = i64::from(STRAIGHT_COST);
+            let diagonal = i64::from(DIAGONAL_COST);
+            (straight * dx.max(dy) + (diagonal - straight) * dx.min(dy)) as u32
+        };
+
+        let mut cost = vec![u32::MAX; self.blocked.len()];
+        let mut came_from: Vec<Option<(usize, usize)>> = vec![None; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+        cost[index(start_cell)] = 0;
+        open.push(Reverse((heuristic(start_cell), 0, start_cell)));
+
+        while let Some(Reverse((_, so_far, cell))) = open.pop() {
+            if cell == goal_cell {
+                break;
+            }
+            if so_far > cost[index(cell)] {
+                continue;
+            }
+            for (next, step) in self.neighbours(cell) {
+                let next_cost = so_far + step;
+                if next_cost < cost[index(next)] {
+                    cost[index(next)] = next_cost;
+                    came_from[index(next)] = Some(cell);
+                    open.push(Reverse((next_cost + heuristic(next), next_cost, next)));
+                }
+            }
+        }
+        if cost[index(goal_cell)] == u32::MAX {
+            return None;
+        }
+
+        let mut cells = vec![goal_cell];
+        while let Some(previous) = came_from[index(*cells.last().expect("Path is never empty"))] {
+            cells.push(previous);
+        }
+        cells.reverse();
+
+        // Skip every corner we can see past.
+        let mut corners = Vec::new();
+        let mut from = start;
+        for (i, &cell) in cells.iter().enumerate() {
+            let next = cells.get(i + 1).map_or(goal, |&next| self.center_of(next));
+            if !self.line_of_sight(from, next) {
+                from = self.center_of(cell);
+                corners.push(from);
+            }
+        }
+        Some(corners)
+    }
+
+    fn clamped_cell(&self, point: Vector2<f32>) -> (usize, usize) {
+        let column = (point.x / self.cell).floor().max(0.0) as usize;
+        let row = (point.y / self.cell).floor().max(0.0) as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+
+    /// Free cells around `cell`, without cutting the corners of blocked ones.
+    fn neighbours(&self, cell: (usize, usize)) -> Vec<((usize, usize), u32)> {
+        let free = |column: i64, row: i64| {
+            column >= 0
+                && row >= 0
+                && (column as usize) < self.columns
+                && (row as usize) < self.rows
+                && !self.is_blocked((column as usize, row as usize))
+        };
+        let (column, row) = (cell.0 as i64, cell.1 as i64);
+
+        let mut neighbours = Vec::with_capacity(8);
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx == 0 && dy == 0) || !free(column + dx, row + dy) {
+                    continue;
+                }
+                let step = if dx != 0 && dy != 0 {
+                    if !free(column + dx, row) || !free(column, row + dy) {
+                        continue;
+                    }
+                    DIAGONAL_COST
+                } else {
+                    STRAIGHT_COST
+                };
+                neighbours.push((((column + dx) as usize, (row + dy) as usize), step));
+            }
+        }
+        neighbours
+    }
+
+    /// The closest free cell to `cell` by grid steps, if there is one.
+    fn nearest_free(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        let mut seen = vec![false; self.blocked.len()];
+        let mut queue = VecDeque::new();
+        seen[cell.1 * self.columns + cell.0] = true;
+        queue.push_back(cell);
+        while let Some(cell) = queue.pop_front() {
+            if !self.is_blocked(cell) {
+                return Some(cell);
+            }
+            let (column, row) = cell;
+            let around = [
+                (column.wrapping_sub(1), row),
+                (column + 1, row),
+                (column, row.wrapping_sub(1)),
+                (column, row + 1),
+            ];
+            for &(column, row) in around.iter() {
+                if column < self.columns && row < self.rows && !seen[row * self.columns + column] {
+                    seen[row * self.columns + column] = true;
+                    queue.push_back((column, row));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl NavPath {
+    /// Where a floater at `position` heading for `goal` should steer now.
+    /// Plans again if the goal moved more than half a cell or the grid
+    /// changed since the last plan.
+    pub fn next_point(
+        &mut self,
+        grid: &NavGrid,
+        position: Vector2<f32>,
+        goal: Vector2<f32>,
+    ) -> Vector2<f32> {
+        let stale = match self.goal {
+            Some(planned) => (planned - goal).norm() > grid.cell_size() / 2.0,
+            None => true,
+        };
+        if stale || self.revision != grid.revision() {
+            self.waypoints = grid
+                .find_path(position, goal)
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+            self.goal = Some(goal);
+            self.revision = grid.revision();
+        }
+
+        while let Some(&corner) = self.waypoints.front() {
+            if (corner - position).norm() > grid.cell_size() {
+                break;
+            }
+            self.waypoints.pop_front();
+        }
+        self.waypoints.front().copied().unwrap_or(goal)
+    }
+
+    /// Moves past the current corner. Returns false if there was none, in
+    /// which case the floater is at its real target.
+    pub fn advance(&mut self) -> bool {
+        self.waypoints.pop_front().is_some()
+    }
+
+    pub fn waypoints(&self) -> impl Iterator<Item = &Vector2<f32>> {
+        self.waypoints.iter()
+    }
+
+    pub fn clear(&mut self) {
+        *self = NavPath::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ncollide2d::shape::{Cuboid, ShapeHandle};
+
+    /// A 200 by 200 arena split down the middle by a wall `height` tall,
+    /// standing on the top edge.
+    fn split_arena(height: f32) -> NavGrid {
+        let mut physics = PhysicsStruct::new((200.0, 200.0));
+        physics.create_fixed(
+            Vector2::new(100.0, height / 2.0),
+            ShapeHandle::new(Cuboid::new(Vector2::new(5.0, height / 2.0))),
+        );
+        NavGrid::build(&physics, NAV_CELL, 10.0)
+    }
+
+    #[test]
+    fn clear_way_needs_no_corners() {
+        let grid = split_arena(0.0);
+        let path = grid.find_path(Vector2::new(50.0, 100.0), Vector2::new(150.0, 100.0));
+        assert_eq!(path, Some(Vec::new()));
+    }
+
+    #[test]
+    fn route_goes_around_the_wall() {
+        let grid = split_arena(140.0);
+        let start = Vector2::new(50.0, 50.0);
+        let goal = Vector2::new(150.0, 50.0);
+        let corners = grid.find_path(start, goal).expect("There is a gap");
+        assert!(!corners.is_empty());
+        assert!(corners.iter().any(|corner| corner.y > 140.0));
+        assert!(corners
+            .iter()
+            .all(|&corner| !grid.is_blocked(grid.cell_of(corner))));
+
+        // Each leg of the route can be walked straight.
+        let mut from = start;
+        for &corner in corners.iter().chain(std::iter::once(&goal)) {
+            assert!(grid.line_of_sight(from, corner));
+            from = corner;
+        }
+    }
+
+    #[test]
+    fn no_route_through_a_full_wall() {
+        let grid = split_arena(200.0);
+        let path = grid.find_path(Vector2::new(50.0, 50.0), Vector2::new(150.0, 50.0));
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn blocked_start_moves_to_the_nearest_free_cell() {
+        let grid = split_arena(140.0);
+        let start = Vector2::new(100.0, 60.0);
+        assert!(grid.is_blocked(grid.cell_of(start)));
+        let path = grid.find_path(start, Vector2::new(150.0, 180.0));
+        assert!(path.is_some());
+    }
+}
@@ -0,0 +1,1242 @@
+use crate::events::{Contact, PhysicsEvent};
+use crate::scene::{Placement, Scene, SceneBody, SceneError, ShapeDesc};
+use crate::snapshot::{
+    BodyKind, BodySnapshot, ColliderSnapshot, PhysicsSnapshot, RigidBodyState, SnapshotError,
+};
+use crate::spatial::{QueryFilter, RayHit};
+
+use nalgebra::{Isometry2, Point2, Vector2};
+use ncollide2d::bounding_volume::AABB;
+use ncollide2d::pipeline::{CollisionGroups, ContactEvent};
+use ncollide2d::query::{self, Proximity, Ray};
+use ncollide2d::shape::{Ball, Cuboid, Shape, ShapeHandle};
+use nphysics2d::force_generator::DefaultForceGeneratorSet;
+use nphysics2d::joint::DefaultJointConstraintSet;
+use nphysics2d::math::{Force, ForceType, Velocity};
+use nphysics2d::object::{
+    Body, BodyPartHandle, Collider, ColliderDesc, DefaultBodyHandle, DefaultBodySet,
+    DefaultColliderHandle, DefaultColliderSet, Ground, RigidBody, RigidBodyDesc,
+};
+use nphysics2d::world::{DefaultGeometricalWorld, DefaultMechanicalWorld};
+use rand::Rng;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// A body handle that can't be used for what was asked of it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhysicsError {
+    /// The body was removed, or the handle is from another world.
+    MissingBody(DefaultBodyHandle),
+    /// The body is there but doesn't move, like a wall or sensor.
+    NotRigid(DefaultBodyHandle),
+    /// The body is there but has no collider to give a shape or position.
+    NoCollider(DefaultBodyHandle),
+}
+
+pub struct PhysicsStruct {
+    mechanical_world: DefaultMechanicalWorld<f32>,
+    geometrical_world: DefaultGeometricalWorld<f32>,
+    pub bodies: DefaultBodySet<f32>,
+    pub colliders: DefaultColliderSet<f32>,
+    joint_constraints: DefaultJointConstraintSet<f32>,
+    force_generators: DefaultForceGeneratorSet<f32>,
+    dims: (f32, f32),
+    /// What happened during the last step.
+    events: Vec<PhysicsEvent>,
+    /// Contacts and overlaps ended by removing bodies since the last step,
+    /// reported with the next step's events.
+    removed: Vec<PhysicsEvent>,
+}
+
+impl PhysicsStruct {
+    pub fn new(dims: (f32, f32)) -> Self {
+        PhysicsStruct::with_gravity(dims, Vector2::new(0.0, 0.0))
+    }
+
+    pub fn with_gravity(dims: (f32, f32), gravity: Vector2<f32>) -> Self {
+        let mut physics = PhysicsStruct::empty(dims, gravity);
+        PhysicsStruct::initialize_walls(&mut physics.bodies, &mut physics.colliders, dims, true);
+        physics
+    }
+
+    /// Walls on three sides only, with the top left open.
+    pub fn without_ceiling(dims: (f32, f32), gravity: Vector2<f32>) -> Self {
+        let mut physics = PhysicsStruct::empty(dims, gravity);
+        PhysicsStruct::initialize_walls(&mut physics.bodies, &mut physics.colliders, dims, false);
+        physics
+    }
+
+    fn empty(dims: (f32, f32), gravity: Vector2<f32>) -> Self {
+        let mechanical_world = DefaultMechanicalWorld::new(gravity);
+        let geometrical_world = DefaultGeometricalWorld::new();
+        let bodies = DefaultBodySet::new();
+        let colliders = DefaultColliderSet::new();
+        let joint_constraints = DefaultJointConstraintSet::new();
+        let force_generators = DefaultForceGeneratorSet::new();
+
+        PhysicsStruct {
+            mechanical_world,
+            geometrical_world,
+            bodies,
+            colliders,
+            joint_constraints,
+            force_generators,
+            dims,
+            events: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Builds a walled world from `scene`, returning every body it created in
+    /// the order they are listed in the scene.
+    pub fn from_scene<R: Rng>(
+        scene: &Scene,
+        rng: &mut R,
+    ) -> Result<(Self, Vec<SceneBody>), SceneError> {
+        scene.validate()?;
+
+        let dims = (scene.arena.width, scene.arena.height);
+        let gravity = Vector2::new(scene.gravity.0, scene.gravity.1);
+        let mut physics = if scene.arena.ceiling {
+            PhysicsStruct::with_gravity(dims, gravity)
+        } else {
+            PhysicsStruct::without_ceiling(dims, gravity)
+        };
+
+        let mut created = Vec::new();
+        for desc in scene.bodies.iter() {
+            for _ in 0..desc.count {
+                let margin = desc.shape.bounding_radius();
+                let position = match desc.position {
+                    Placement::At(x, y) => Vector2::new(x, y),
+                    Placement::Random => Vector2::new(
+                        rng.gen_range(margin, dims.0 - margin),
+                        rng.gen_range(margin, dims.1 - margin),
+                    ),
+                };
+
+                let shape = match desc.shape {
+                    ShapeDesc::Ball { radius } => ShapeHandle::new(Ball::new(radius)),
+                    ShapeDesc::Cuboid {
+                        half_width,
+                        half_height,
+                    } => ShapeHandle::new(Cuboid::new(Vector2::new(half_width, half_height))),
+                };
+
+                let handle = if desc.fixed {
+                    physics.create_fixed(position, shape)
+                } else {
+                    let rigid_body = RigidBodyDesc::new()
+                        .translation(position)
+                        .velocity(Velocity::linear(desc.velocity.0, desc.velocity.1))
+                        .linear_damping(desc.linear_damping)
+                        .build();
+                    let handle = physics.bodies.insert(rigid_body);
+                    let co = ColliderDesc::new(shape)
+                        .density(desc.density)
+                        .build(BodyPartHandle(handle, 0));
+                    physics.colliders.insert(co);
+                    handle
+                };
+
+                created.push(SceneBody {
+                    handle,
+                    role: desc.role,
+                    shape: desc.shape,
+                    color: desc.color(),
+                    arrival: desc.arrival,
+                });
+            }
+        }
+
+        physics.sync_geometry();
+        Ok((physics, created))
+    }
+
+    /// Captures every body and collider. The returned handles line up with
+    /// `PhysicsSnapshot::bodies`, so callers can turn their own handles into indices.
+    pub fn snapshot(&self) -> (PhysicsSnapshot, Vec<DefaultBodyHandle>) {
+        let mut handles = Vec::new();
+        let mut bodies = Vec::new();
+        for (handle, _) in self.bodies.iter() {
+            let kind = match self.bodies.rigid_body(handle) {
+                Some(body) => {
+                    let position = body.position();
+                    let velocity = body.velocity();
+                    BodyKind::Rigid(RigidBodyState {
+                        position: (position.translation.x, position.translation.y),
+                        rotation: position.rotation.angle(),
+                        linear_velocity: (velocity.linear.x, velocity.linear.y),
+                        angular_velocity: velocity.angular,
+                        linear_damping: body.linear_damping(),
+                        angular_damping: body.angular_damping(),
+                    })
+                }
+                None => BodyKind::Ground,
+            };
+
+            let mut colliders = Vec::new();
+            for (_, collider) in self.colliders.iter() {
+                if collider.body() != handle {
+                    continue;
+                }
+                let shape = collider.shape();
+                let shape = if let Some(ball) = shape.as_shape::<Ball<f32>>() {
+                    ShapeDesc::Ball {
+                        radius: ball.radius(),
+                    }
+                } else if let Some(cuboid) = shape.as_shape::<Cuboid<f32>>() {
+                    let half_extents = cuboid.half_extents();
+                    ShapeDesc::Cuboid {
+                        half_width: half_extents.x,
+                        half_height: half_extents.y,
+                    }
+                } else {
+                    continue;
+                };
+                let offset = collider.position_wrt_body();
+                colliders.push(ColliderSnapshot {
+                    shape,
+                    position: (offset.translation.x, offset.translation.y),
+                    rotation: offset.rotation.angle(),
+                    density: collider.density(),
+                    sensor: collider.is_sensor(),
+                });
+            }
+
+            handles.push(handle);
+            bodies.push(BodySnapshot { kind, colliders });
+        }
+
+        let gravity = self.gravity();
+        let snapshot = PhysicsSnapshot {
+            dims: self.dims,
+            gravity: (gravity.x, gravity.y),
+            timestep: self.timestep(),
+            bodies,
+        };
+
+        (snapshot, handles)
+    }
+
+    /// Rebuilds a world from `snapshot`. The returned handles are indexed like
+    /// `PhysicsSnapshot::bodies`. Cached contact data isn't part of a snapshot,
+    /// so busy scenes slowly drift from the original run after a restore.
+    pub fn from_snapshot(
+        snapshot: &PhysicsSnapshot,
+    ) -> Result<(Self, Vec<DefaultBodyHandle>), SnapshotError> {
+        let gravity = Vector2::new(snapshot.gravity.0, snapshot.gravity.1);
+        let mut physics = PhysicsStruct::empty(snapshot.dims, gravity);
+        physics.set_timestep(snapshot.timestep);
+
+        let mut handles = Vec::new();
+        for body in snapshot.bodies.iter() {
+            let handle = match body.kind {
+                BodyKind::Ground => physics.bodies.insert(Ground::new()),
+                BodyKind::Rigid(state) => {
+                    let rigid_body = RigidBodyDesc::new()
+                        .position(Isometry2::new(
+                            Vector2::new(state.position.0, state.position.1),
+                            state.rotation,
+                        ))
+                        .velocity(Velocity::new(
+                            Vector2::new(state.linear_velocity.0, state.linear_velocity.1),
+                            state.angular_velocity,
+                        ))
+                        .linear_damping(state.linear_damping)
+                        .angular_damping(state.angular_damping)
+                        .build();
+                    physics.bodies.insert(rigid_body)
+                }
+            };
+
+            for collider in body.colliders.iter() {
+                let shape = match collider.shape {
+                    ShapeDesc::Ball { radius } if radius > 0.0 => {
+                        ShapeHandle::new(Ball::new(radius))
+                    }
+                    ShapeDesc::Cuboid {
+                        half_width,
+                        half_height,
+                    } if half_width > 0.0 && half_height > 0.0 => {
+                        ShapeHandle::new(Cuboid::new(Vector2::new(half_width, half_height)))
+                    }
+                    shape => {
+                        return Err(SnapshotError::Invalid(format!(
+                            "collider shape {:?} has a non-positive size",
+                            shape
+                        )))
+                    }
+                };
+                let co = ColliderDesc::new(shape)
+                    .position(Isometry2::new(
+                        Vector2::new(collider.position.0, collider.position.1),
+                        collider.rotation,
+                    ))
+                    .density(collider.density)
+                    .sensor(collider.sensor)
+                    .build(BodyPartHandle(handle, 0));
+                physics.colliders.insert(co);
+            }
+
+            handles.push(handle);
+        }
+
+        physics.sync_geometry();
+        Ok((physics, handles))
+    }
+
+    pub fn dims(&self) -> (f32, f32) {
+        self.dims
+    }
+
+    pub fn step(&mut self) {
+        // Velocities going in, to tell how hard contacts pushed.
+        let before: HashMap<DefaultBodyHandle, Vector2<f32>> = self
+            .bodies
+            .iter()
+            .filter_map(|(handle, _)| {
+                Some((handle, self.bodies.rigid_body(handle)?.velocity().linear))
+            })
+            .collect();
+        self.mechanical_world.step(
+            &mut self.geometrical_world,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.joint_constraints,
+            &mut self.force_generators,
+        );
+        let mut events = std::mem::take(&mut self.removed);
+        events.extend(self.collect_events(&before));
+        self.events = events;
+    }
+
+    /// Contacts and sensor overlaps that began or ended during the last
+    /// step, in terms of bodies rather than colliders.
+    pub fn events(&self) -> &[PhysicsEvent] {
+        &self.events
+    }
+
+    /// Brings the collision world up to date with the bodies. Stepping does
+    /// this anyway, so it's only needed for queries made after adding or
+    /// moving bodies by hand.
+    pub fn sync_geometry(&mut self) {
+        self.geometrical_world
+            .maintain(&mut self.bodies, &mut self.colliders);
+        self.geometrical_world
+            .sync_colliders(&self.bodies, &mut self.colliders);
+        self.geometrical_world.perform_broad_phase(&self.colliders);
+    }
+
+    pub fn timestep(&self) -> f32 {
+        self.mechanical_world.timestep()
+    }
+
+    pub fn set_timestep(&mut self, dt: f32) {
+        self.mechanical_world.set_timestep(dt);
+    }
+
+    pub fn gravity(&self) -> Vector2<f32> {
+        self.mechanical_world.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector2<f32>) {
+        self.mechanical_world.gravity = gravity;
+    }
+
+    pub fn create_ball(
+        &mut self,
+        radius: f32,
+        start_pos: Vector2<f32>,
+        density: f32,
+    ) -> DefaultBodyHandle {
+        self.create_ball_with_damping(radius, start_pos, density, 1.0)
+    }
+
+    pub fn create_ball_with_damping(
+        &mut self,
+        radius: f32,
+        start_pos: Vector2<f32>,
+        density: f32,
+        linear_damping: f32,
+    ) -> DefaultBodyHandle {
+        let ball_shape = ShapeHandle::new(Ball::new(radius));
+        let rigid_body = RigidBodyDesc::new()
+            .translation(start_pos)
+            .linear_damping(linear_damping)
+            .build();
+
+        let ball = self.bodies.insert(rigid_body);
+        let co = ColliderDesc::new(ball_shape)
+            .density(density)
+            .build(BodyPartHandle(ball, 0));
+        self.colliders.insert(co);
+
+        ball
+    }
+
+    /// Adds an immovable collider, like a wall or an obstacle.
+    pub fn create_fixed(
+        &mut self,
+        position: Vector2<f32>,
+        shape: ShapeHandle<f32>,
+    ) -> DefaultBodyHandle {
+        let handle = self.bodies.insert(Ground::new());
+        let co = ColliderDesc::new(shape)
+            .translation(position)
+            .build(BodyPartHandle(handle, 0));
+        self.colliders.insert(co);
+        handle
+    }
+
+    /// Adds an immovable sensor: it reports bodies entering it rather than
+    /// stopping them.
+    pub fn create_sensor(
+        &mut self,
+        position: Vector2<f32>,
+        shape: ShapeHandle<f32>,
+    ) -> DefaultBodyHandle {
+        let handle = self.bodies.insert(Ground::new());
+        let co = ColliderDesc::new(shape)
+            .translation(position)
+            .sensor(true)
+            .build(BodyPartHandle(handle, 0));
+        self.colliders.insert(co);
+        handle
+    }
+
+    /// Bodies that started touching a sensor during the last step, as
+    /// (sensor, body) pairs.
+    pub fn sensor_entries(&self) -> Vec<(DefaultBodyHandle, DefaultBodyHandle)> {
+        self.events
+            .iter()
+            .filter_map(|event| match *event {
+                PhysicsEvent::SensorEntered { sensor, body } => Some((sensor, body)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Removes a body along with its colliders.
+    pub fn remove_body(&mut self, object_handle: DefaultBodyHandle) {
+        let colliders: Vec<_> = self
+            .colliders
+            .iter()
+            .filter(|(_, collider)| collider.body() == object_handle)
+            .map(|(handle, _)| handle)
+            .collect();
+        // The narrow phase forgets a removed collider's pairs without
+        // reporting them, so end them here.
+        self.sync_geometry();
+        for &handle in colliders.iter() {
+            self.removed.extend(self.ended_by_removing(handle));
+        }
+        for handle in colliders {
+            self.colliders.remove(handle);
+        }
+        self.bodies.remove(object_handle);
+        self.sync_geometry();
+    }
+
+    /// The first immovable body with a collider covering `point`.
+    pub fn fixed_body_at(&self, point: Vector2<f32>) -> Option<DefaultBodyHandle> {
+        self.bodies_at(point, &QueryFilter::default())
+            .into_iter()
+            .find(|&handle| self.is_fixed(handle))
+    }
+
+    /// Whether `object_handle` still refers to a body in the world.
+    pub fn contains_body(&self, object_handle: DefaultBodyHandle) -> bool {
+        self.bodies.get(object_handle).is_some()
+    }
+
+    /// Whether `object_handle` is a body that moves, as opposed to a wall,
+    /// fixed prop or sensor, or a body that's gone.
+    pub fn is_rigid(&self, object_handle: DefaultBodyHandle) -> bool {
+        self.bodies.rigid_body(object_handle).is_some()
+    }
+
+    /// Panics if `object_handle` isn't a rigid body, as do the other
+    /// lookups without `try_`.
+    pub fn apply_force(&mut self, object_handle: DefaultBodyHandle, force_vector: Vector2<f32>) {
+        self.try_apply_force(object_handle, force_vector)
+            .expect("Object not found");
+    }
+
+    pub fn try_apply_force(
+        &mut self,
+        object_handle: DefaultBodyHandle,
+        force_vector: Vector2<f32>,
+    ) -> Result<(), PhysicsError> {
+        let body = self.rigid_body_mut(object_handle)?;
+        body.apply_force(0, &Force::new(force_vector, 0.0), ForceType::Force, true);
+        Ok(())
+    }
+
+    pub fn apply_impulse(&mut self, object_handle: DefaultBodyHandle, impulse: Vector2<f32>) {
+        self.try_apply_impulse(object_handle, impulse)
+            .expect("Object not found");
+    }
+
+    pub fn try_apply_impulse(
+        &mut self,
+        object_handle: DefaultBodyHandle,
+        impulse: Vector2<f32>,
+    ) -> Result<(), PhysicsError> {
+        let body = self.rigid_body_mut(object_handle)?;
+        body.apply_force(0, &Force::new(impulse, 0.0), ForceType::Impulse, true);
+        Ok(())
+    }
+
+    pub fn get_pos_of(&self, object_handle: DefaultBodyHandle) -> Vector2<f32> {
+        self.try_get_pos_of(object_handle)
+            .expect("Object not found")
+    }
+
+    pub fn try_get_pos_of(
+        &self,
+        object_handle: DefaultBodyHandle,
+    ) -> Result<Vector2<f32>, PhysicsError> {
+        Ok(self
+            .rigid_body(object_handle)?
+            .position()
+            .translation
+            .vector)
+    }
+
+    pub fn get_vel_of(&self, object_handle: DefaultBodyHandle) -> Vector2<f32> {
+        self.try_get_vel_of(object_handle)
+            .expect("Object not found")
+    }
+
+    pub fn try_get_vel_of(
+        &self,
+        object_handle: DefaultBodyHandle,
+    ) -> Result<Vector2<f32>, PhysicsError> {
+        Ok(self.rigid_body(object_handle)?.velocity().linear)
+    }
+
+    pub fn get_mass_of(&self, object_handle: DefaultBodyHandle) -> f32 {
+        self.try_get_mass_of(object_handle)
+            .expect("Object not found")
+    }
+
+    pub fn try_get_mass_of(&self, object_handle: DefaultBodyHandle) -> Result<f32, PhysicsError> {
+        Ok(self.rigid_body(object_handle)?.augmented_mass().linear)
+    }
+
+    pub fn get_state_of(
+        &self,
+        object_handle: DefaultBodyHandle,
+    ) -> (Isometry2<f32>, Velocity<f32>) {
+        self.try_get_state_of(object_handle)
+            .expect("Object not found")
+    }
+
+    pub fn try_get_state_of(
+        &self,
+        object_handle: DefaultBodyHandle,
+    ) -> Result<(Isometry2<f32>, Velocity<f32>), PhysicsError> {
+        let body = self.rigid_body(object_handle)?;
+        Ok((*body.position(), *body.velocity()))
+    }
+
+    pub fn set_state_of(
+        &mut self,
+        object_handle: DefaultBodyHandle,
+        position: Isometry2<f32>,
+        velocity: Velocity<f32>,
+    ) {
+        self.try_set_state_of(object_handle, position, velocity)
+            .expect("Object not found");
+    }
+
+    pub fn try_set_state_of(
+        &mut self,
+        object_handle: DefaultBodyHandle,
+        position: Isometry2<f32>,
+        velocity: Velocity<f32>,
+    ) -> Result<(), PhysicsError> {
+        let body = self.rigid_body_mut(object_handle)?;
+        body.set_position(position);
+        body.set_velocity(velocity);
+        body.activate();
+        Ok(())
+    }
+
+    pub fn get_collider_pos_of(&self, object_handle: DefaultBodyHandle) -> Isometry2<f32> {
+        self.try_get_collider_pos_of(object_handle)
+            .expect("Object not found")
+    }
+
+    /// Unlike the other lookups this works for fixed bodies too, as long as
+    /// they have a collider.
+    pub fn try_get_collider_pos_of(
+        &self,
+        object_handle: DefaultBodyHandle,
+    ) -> Result<Isometry2<f32>, PhysicsError> {
+        if !self.contains_body(object_handle) {
+            return Err(PhysicsError::MissingBody(object_handle));
+        }
+        self.colliders
+            .iter()
+            .find(|(_, collider)| collider.body() == object_handle)
+            .map(|(_, collider)| *collider.position())
+            .ok_or(PhysicsError::NoCollider(object_handle))
+    }
+
+    /// Whether `query` falls inside the body. `radius` is ignored: the
+    /// body's own colliders are checked instead.
+    #[deprecated(
+        since = "0.1.0",
+        note = "use `bodies_at`, which works for bodies of any shape; `radius` is ignored"
+    )]
+    pub fn floater_contains(
+        &self,
+        object_handle: DefaultBodyHandle,
+        _radius: f32,
+        query: Vector2<f32>,
+    ) -> bool {
+        self.bodies_at(query, &QueryFilter::default())
+            .contains(&object_handle)
+    }
+
+    /// Bodies whose centre lies between `mins` and `maxs`. Candidates come
+    /// from the broad phase, so this sees colliders as of the last step.
+    pub fn bodies_in_aabb(&self, mins: Vector2<f32>, maxs: Vector2<f32>) -> Vec<DefaultBodyHandle> {
+        let aabb = AABB::new(Point2::from(mins), Point2::from(maxs));
+        let groups = CollisionGroups::new();
+
+        let mut found = Vec::new();
+        for (_, collider) in
+            self.geometrical_world
+                .interferences_with_aabb(&self.colliders, &aabb, &groups)
+        {
+            let handle = collider.body();
+            if found.contains(&handle) {
+                continue;
+            }
+            if let Some(body) = self.bodies.rigid_body(handle) {
+                let center = Point2::from(body.position().translation.vector);
+                if aabb.contains_local_point(&center) {
+                    found.push(handle);
+                }
+            }
+        }
+        found
+    }
+
+    /// Bodies whose centre is within `radius` of `center`, found the same way
+    /// as `bodies_in_aabb`.
+    pub fn bodies_within(&self, center: Vector2<f32>, radius: f32) -> Vec<DefaultBodyHandle> {
+        let reach = Vector2::new(radius, radius);
+        self.bodies_in_aabb(center - reach, center + reach)
+            .into_iter()
+            .filter(|&handle| (self.get_pos_of(handle) - center).norm() <= radius)
+            .collect()
+    }
+
+    /// The first body along the ray from `origin` toward `direction`, no
+    /// further than `max_distance`. A ray starting inside a body hits it
+    /// straight away. Like every query here, candidates come from the broad
+    /// phase, so bodies moved by hand need a `sync_geometry` first.
+    pub fn cast_ray(
+        &self,
+        origin: Vector2<f32>,
+        direction: Vector2<f32>,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<RayHit> {
+        self.cast_ray_all(origin, direction, max_distance, filter)
+            .into_iter()
+            .next()
+    }
+
+    /// Every body along the ray, nearest first, each hit where the ray
+    /// first meets it.
+    pub fn cast_ray_all(
+        &self,
+        origin: Vector2<f32>,
+        direction: Vector2<f32>,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Vec<RayHit> {
+        let direction = match direction.try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => return Vec::new(),
+        };
+        let ray = Ray::new(Point2::from(origin), direction);
+        let groups = CollisionGroups::new();
+
+        let mut hits: Vec<RayHit> = Vec::new();
+        for (_, collider, intersection) in self.geometrical_world.interferences_with_ray(
+            &self.colliders,
+            &ray,
+            max_distance,
+            &groups,
+        ) {
+            if !self.passes(filter, collider) {
+                continue;
+            }
+            let hit = RayHit {
+                body: collider.body(),
+                point: origin + direction * intersection.toi,
+                normal: intersection.normal,
+                distance: intersection.toi,
+            };
+            // A body with several colliders is hit where it's first met.
+            match hits.iter_mut().find(|seen| seen.body == hit.body) {
+                Some(seen) if hit.distance < seen.distance => *seen = hit,
+                Some(_) => (),
+                None => hits.push(hit),
+            }
+        }
+        hits.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(Ordering::Equal)
+        });
+        hits
+    }
+
+    /// Every body with a collider covering `point`, whatever its shape.
+    pub fn bodies_at(&self, point: Vector2<f32>, filter: &QueryFilter) -> Vec<DefaultBodyHandle> {
+        let point = Point2::from(point);
+        let groups = CollisionGroups::new();
+        let mut found = Vec::new();
+        for (_, collider) in
+            self.geometrical_world
+                .interferences_with_point(&self.colliders, &point, &groups)
+        {
+            let handle = collider.body();
+            if self.passes(filter, collider) && !found.contains(&handle) {
+                found.push(handle);
+            }
+        }
+        found
+    }
+
+    /// The body under `point`, for picking. Movable bodies win over fixed
+    /// ones, so a floater on top of a prop is the one found.
+    pub fn body_at(&self, point: Vector2<f32>, filter: &QueryFilter) -> Option<DefaultBodyHandle> {
+        let found = self.bodies_at(point, filter);
+        found
+            .iter()
+            .copied()
+            .find(|&handle| !self.is_fixed(handle))
+            .or_else(|| found.first().copied())
+    }
+
+    /// Every body with a collider touching the box from `mins` to `maxs`.
+    /// Unlike `bodies_in_aabb`, a body only partly inside counts.
+    pub fn bodies_overlapping_aabb(
+        &self,
+        mins: Vector2<f32>,
+        maxs: Vector2<f32>,
+        filter: &QueryFilter,
+    ) -> Vec<DefaultBodyHandle> {
+        let center = (mins + maxs) / 2.0;
+        let area = Cuboid::new(((maxs - mins) / 2.0).map(|extent| extent.max(0.0)));
+        self.bodies_overlapping(&area, &Isometry2::translation(center.x, center.y), filter)
+    }
+
+    /// Every body with a collider touching `shape` placed at `position`.
+    pub fn bodies_overlapping(
+        &self,
+        shape: &dyn Shape<f32>,
+        position: &Isometry2<f32>,
+        filter: &QueryFilter,
+    ) -> Vec<DefaultBodyHandle> {
+        let aabb = shape.aabb(position);
+        let groups = CollisionGroups::new();
+        let mut found = Vec::new();
+        for (_, collider) in
+            self.geometrical_world
+                .interferences_with_aabb(&self.colliders, &aabb, &groups)
+        {
+            let handle = collider.body();
+            if !self.passes(filter, collider) || found.contains(&handle) {
+                continue;
+            }
+            let proximity =
+                query::proximity(position, shape, collider.position(), collider.shape(), 0.0);
+            if proximity == Proximity::Intersecting {
+                found.push(handle);
+            }
+        }
+        found
+    }
+
+    /// The body nearest `point` within `max_distance`, measured to its
+    /// surface, and how far that is. Zero means `point` is inside it.
+    pub fn nearest_body(
+        &self,
+        point: Vector2<f32>,
+        max_distance: f32,
+        filter: &QueryFilter,
+    ) -> Option<(DefaultBodyHandle, f32)> {
+        let reach = Vector2::new(max_distance, max_distance);
+        let aabb = AABB::new(Point2::from(point - reach), Point2::from(point + reach));
+        let groups = CollisionGroups::new();
+        let point = Point2::from(point);
+        self.geometrical_world
+            .interferences_with_aabb(&self.colliders, &aabb, &groups)
+            .filter(|(_, collider)| self.passes(filter, collider))
+            .filter_map(|(_, collider)| {
+                let distance = collider.shape().as_point_query()?.distance_to_point(
+                    collider.position(),
+                    &point,
+                    true,
+                );
+                Some((collider.body(), distance))
+            })
+            .filter(|&(_, distance)| distance <= max_distance)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+    }
+
+    /// Whether `filter` lets a query see `collider`.
+    fn passes(&self, filter: &QueryFilter, collider: &Collider<f32, DefaultBodyHandle>) -> bool {
+        let handle = collider.body();
+        if collider.is_sensor() && !filter.sensors {
+            return false;
+        }
+        if filter.exclude.contains(&handle) {
+            return false;
+        }
+        !(filter.skip_fixed && self.is_fixed(handle))
+    }
+
+    fn rigid_body(&self, handle: DefaultBodyHandle) -> Result<&RigidBody<f32>, PhysicsError> {
+        if !self.contains_body(handle) {
+            return Err(PhysicsError::MissingBody(handle));
+        }
+        self.bodies
+            .rigid_body(handle)
+            .ok_or(PhysicsError::NotRigid(handle))
+    }
+
+    fn rigid_body_mut(
+        &mut self,
+        handle: DefaultBodyHandle,
+    ) -> Result<&mut RigidBody<f32>, PhysicsError> {
+        if !self.contains_body(handle) {
+            return Err(PhysicsError::MissingBody(handle));
+        }
+        self.bodies
+            .rigid_body_mut(handle)
+            .ok_or(PhysicsError::NotRigid(handle))
+    }
+
+    fn is_fixed(&self, handle: DefaultBodyHandle) -> bool {
+        matches!(self.bodies.get(handle), Some(body) if body.is_ground())
+    }
+
+    fn initialize_walls(
+        bodies: &mut DefaultBodySet<f32>,
+        colliders: &mut DefaultColliderSet<f32>,
+        dims: (f32, f32),
+        ceiling: bool,
+    ) {
+        // Ground & Ceiling
+        let win_width = dims.0;
+        let win_height = dims.1;
+
+        let ground_size = win_width;
+        let ground_shape = ShapeHandle::new(Cuboid::new(Vector2::new(ground_size, 1.0)));
+
+        let ground_handle = bodies.insert(Ground::new());
+        let co = ColliderDesc::new(ground_shape.clone())
+            .translation(Vector2::new(0.0, win_height))
+            .build(BodyPartHandle(ground_handle, 0));
+        colliders.insert(co);
+        if ceiling {
+            let ceil_handle = bodies.insert(Ground::new());
+            let ceil_co = ColliderDesc::new(ground_shape)
+                .translation(Vector2::new(0.0, 0.0))
+                .build(BodyPartHandle(ceil_handle, 0));
+            colliders.insert(ceil_co);
+        }
+
+        // Walls
+
+        let wall_size = win_height;
+        let wall_shape = ShapeHandle::new(Cuboid::new(Vector2::new(1.0, wall_size)));
+        let wall_handle_l = bodies.insert(Ground::new());
+        let left_wall_co = ColliderDesc::new(wall_shape.clone())
+            .translation(Vector2::new(0.0, 0.0))
+            .build(BodyPartHandle(wall_handle_l, 0));
+        let wall_handle_r = bodies.insert(Ground::new());
+        let right_wall_co = ColliderDesc::new(wall_shape)
+            .translation(Vector2::new(win_width, 0.0))
+            .build(BodyPartHandle(wall_handle_r, 0));
+        colliders.insert(left_wall_co);
+        colliders.insert(right_wall_co);
+    }
+
+    /// Reads the narrow phase's contact and proximity events for the step
+    /// just taken. `before` holds each rigid body's velocity going in.
+    fn collect_events(
+        &self,
+        before: &HashMap<DefaultBodyHandle, Vector2<f32>>,
+    ) -> Vec<PhysicsEvent> {
+        let mut events = Vec::new();
+        for event in self.geometrical_world.contact_events().iter() {
+            match *event {
+                ContactEvent::Started(collider1, collider2) => {
+                    if let Some(contact) = self.contact_between(collider1, collider2, before) {
+                        events.push(PhysicsEvent::ContactStarted(contact));
+                    }
+                }
+                ContactEvent::Stopped(collider1, collider2) => {
+                    if let (Some(first), Some(second)) =
+                        (self.colliders.get(collider1), self.colliders.get(collider2))
+                    {
+                        events.push(PhysicsEvent::ContactStopped {
+                            body1: first.body(),
+                            body2: second.body(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for event in self.geometrical_world.proximity_events().iter() {
+            let (first, second) = match (
+                self.colliders.get(event.collider1),
+                self.colliders.get(event.collider2),
+            ) {
+                (Some(first), Some(second)) => (first, second),
+                _ => continue,
+            };
+            let (sensor, body) = if first.is_sensor() {
+                (first.body(), second.body())
+            } else {
+                (second.body(), first.body())
+            };
+            if event.new_status == Proximity::Intersecting {
+                events.push(PhysicsEvent::SensorEntered { sensor, body });
+            } else if event.prev_status == Proximity::Intersecting {
+                events.push(PhysicsEvent::SensorExited { sensor, body });
+            }
+        }
+        events
+    }
+
+    /// Stops and exits for every contact and sensor overlap `collider` is
+    /// part of, as if it had just moved away.
+    fn ended_by_removing(&self, collider: DefaultColliderHandle) -> Vec<PhysicsEvent> {
+        let mut events = Vec::new();
+        if let Some(contacts) =
+            self.geometrical_world
+                .contacts_with(&self.colliders, collider, true)
+        {
+            for (_, first, _, second, _, _) in contacts {
+                events.push(PhysicsEvent::ContactStopped {
+                    body1: first.body(),
+                    body2: second.body(),
+                });
+            }
+        }
+        if let Some(proximities) =
+            self.geometrical_world
+                .proximities_with(&self.colliders, collider, true)
+        {
+            for (_, first, _, second, _, proximity) in proximities {
+                if proximity != Proximity::Intersecting {
+                    continue;
+                }
+                let (sensor, body) = if first.is_sensor() {
+                    (first.body(), second.body())
+                } else {
+                    (second.body(), first.body())
+                };
+                events.push(PhysicsEvent::SensorExited { sensor, body });
+            }
+        }
+        events
+    }
+
+    /// The deepest point of contact between two colliders, with an impulse
+    /// estimated from how fast the bodies were closing going into the step.
+    fn contact_between(
+        &self,
+        collider1: DefaultColliderHandle,
+        collider2: DefaultColliderHandle,
+        before: &HashMap<DefaultBodyHandle, Vector2<f32>>,
+    ) -> Option<Contact> {
+        let (_, first, _, second, _, manifold) =
+            self.geometrical_world
+                .contact_pair(&self.colliders, collider1, collider2, true)?;
+        let deepest = manifold.deepest_contact()?;
+        let normal = deepest.contact.normal.into_inner();
+        let point = (deepest.contact.world1.coords + deepest.contact.world2.coords) / 2.0;
+        let (body1, body2) = (first.body(), second.body());
+
+        // Momentum the collision has to take out of the closing speed.
+        // Bodies that aren't rigid bodies don't move, and weigh as much as
+        // it takes.
+        let motion = |body: DefaultBodyHandle| match self.bodies.rigid_body(body) {
+            Some(rigid) => (
+                before.get(&body).copied().unwrap_or_else(Vector2::zeros),
+                1.0 / rigid.augmented_mass().linear,
+            ),
+            None => (Vector2::zeros(), 0.0),
+        };
+        let (velocity1, inverse_mass1) = motion(body1);
+        let (velocity2, inverse_mass2) = motion(body2);
+        let closing = (velocity1 - velocity2).dot(&normal).max(0.0);
+        let inverse_mass = inverse_mass1 + inverse_mass2;
+        let closing_impulse_estimate = if inverse_mass > 0.0 {
+            closing / inverse_mass
+        } else {
+            0.0
+        };
+
+        Some(Contact {
+            body1,
+            body2,
+            point,
+            normal,
+            closing_impulse_estimate,
+        })
+    }
+}
+
+impl fmt::Display for PhysicsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhysicsError::MissingBody(handle) => write!(f, "body {:?} does not exist", handle),
+            PhysicsError::NotRigid(handle) => write!(f, "body {:?} is not a rigid body", handle),
+            PhysicsError::NoCollider(handle) => write!(f, "body {:?} has no collider", handle),
+        }
+    }
+}
+
+impl Error for PhysicsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steps `physics` up to `steps` times, collecting the events that
+    /// involve `body`.
+    fn events_of(
+        physics: &mut PhysicsStruct,
+        body: DefaultBodyHandle,
+        steps: usize,
+    ) -> Vec<PhysicsEvent> {
+        let mut events = Vec::new();
+        for _ in 0..steps {
+            physics.step();
+            events.extend(
+                physics
+                    .events()
+                    .iter()
+                    .copied()
+                    .filter(|event| match *event {
+                        PhysicsEvent::ContactStarted(contact) => contact.seen_from(body).is_some(),
+                        PhysicsEvent::ContactStopped { body1, body2 } => {
+                            body1 == body || body2 == body
+                        }
+                        PhysicsEvent::SensorEntered { body: other, .. }
+                        | PhysicsEvent::SensorExited { body: other, .. } => other == body,
+                    }),
+            );
+        }
+        events
+    }
+
+    fn is_between(event: &PhysicsEvent, a: DefaultBodyHandle, b: DefaultBodyHandle) -> bool {
+        let pair = match *event {
+            PhysicsEvent::ContactStarted(contact) => (contact.body1, contact.body2),
+            PhysicsEvent::ContactStopped { body1, body2 } => (body1, body2),
+            PhysicsEvent::SensorEntered { sensor, body }
+            | PhysicsEvent::SensorExited { sensor, body } => (sensor, body),
+        };
+        pair == (a, b) || pair == (b, a)
+    }
+
+    #[test]
+    fn dropped_ball_starts_and_stops_touching_the_floor() {
+        let mut physics = PhysicsStruct::with_gravity((400.0, 400.0), Vector2::new(0.0, 500.0));
+        let floor = physics.create_fixed(
+            Vector2::new(200.0, 300.0),
+            ShapeHandle::new(Cuboid::new(Vector2::new(100.0, 10.0))),
+        );
+        let ball = physics.create_ball(10.0, Vector2::new(200.0, 250.0), 1.0);
+
+        let events = events_of(&mut physics, ball, 60);
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            PhysicsEvent::ContactStarted(contact) => {
+                let contact = contact.seen_from(ball).unwrap();
+                assert_eq!(contact.body2, floor);
+                assert!(contact.normal.y > 0.9);
+                assert!(contact.closing_impulse_estimate > 0.0);
+            }
+            other => panic!("expected the ball to land, got {:?}", other),
+        }
+
+        physics.set_state_of(
+            ball,
+            Isometry2::new(Vector2::new(200.0, 100.0), 0.0),
+            Velocity::zero(),
+        );
+        let events = events_of(&mut physics, ball, 1);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], PhysicsEvent::ContactStopped { .. }));
+        assert!(is_between(&events[0], ball, floor));
+    }
+
+    #[test]
+    fn body_passing_through_a_sensor_enters_then_exits() {
+        let mut physics = PhysicsStruct::new((400.0, 400.0));
+        let sensor = physics.create_sensor(
+            Vector2::new(200.0, 200.0),
+            ShapeHandle::new(Cuboid::new(Vector2::new(20.0, 20.0))),
+        );
+        let ball = physics.create_ball_with_damping(5.0, Vector2::new(100.0, 200.0), 1.0, 0.0);
+        physics.set_state_of(
+            ball,
+            Isometry2::new(Vector2::new(100.0, 200.0), 0.0),
+            Velocity::linear(200.0, 0.0),
+        );
+
+        let events = events_of(&mut physics, ball, 45);
+        assert_eq!(
+            events,
+            vec![
+                PhysicsEvent::SensorEntered { sensor, body: ball },
+                PhysicsEvent::SensorExited { sensor, body: ball },
+            ]
+        );
+    }
+
+    #[test]
+    fn removing_a_body_ends_its_contacts_and_overlaps() {
+        let mut physics = PhysicsStruct::new((400.0, 400.0));
+        let sensor = physics.create_sensor(
+            Vector2::new(200.0, 200.0),
+            ShapeHandle::new(Cuboid::new(Vector2::new(50.0, 50.0))),
+        );
+        let block = physics.create_fixed(
+            Vector2::new(220.0, 200.0),
+            ShapeHandle::new(Cuboid::new(Vector2::new(10.0, 10.0))),
+        );
+        let ball = physics.create_ball(10.0, Vector2::new(200.0, 200.0), 1.0);
+        let events = events_of(&mut physics, ball, 1);
+        assert!(events.iter().any(|event| is_between(event, ball, sensor)));
+        assert!(events.iter().any(|event| is_between(event, ball, block)));
+
+        physics.remove_body(ball);
+        physics.step();
+        let events = physics.events();
+        assert_eq!(events.len(), 2);
+        assert!(events.contains(&PhysicsEvent::SensorExited { sensor, body: ball }));
+        assert!(events.iter().any(|event| {
+            matches!(event, PhysicsEvent::ContactStopped { .. }) && is_between(event, ball, block)
+        }));
+    }
+
+    #[test]
+    fn ray_hits_come_nearest_first() {
+        let mut physics = PhysicsStruct::empty((400.0, 400.0), Vector2::zeros());
+        let far = physics.create_ball(10.0, Vector2::new(300.0, 200.0), 1.0);
+        let near = physics.create_ball(10.0, Vector2::new(100.0, 200.0), 1.0);
+        let middle = physics.create_ball(10.0, Vector2::new(200.0, 200.0), 1.0);
+        physics.sync_geometry();
+
+        let hits = physics.cast_ray_all(
+            Vector2::new(0.0, 200.0),
+            Vector2::new(1.0, 0.0),
+            1000.0,
+            &QueryFilter::default(),
+        );
+        let bodies: Vec<DefaultBodyHandle> = hits.iter().map(|hit| hit.body).collect();
+        assert_eq!(bodies, vec![near, middle, far]);
+        assert!((hits[0].distance - 90.0).abs() < 1e-3);
+        assert!((hits[0].normal - Vector2::new(-1.0, 0.0)).norm() < 1e-3);
+
+        let short = physics.cast_ray_all(
+            Vector2::new(0.0, 200.0),
+            Vector2::new(1.0, 0.0),
+            150.0,
+            &QueryFilter::default(),
+        );
+        assert_eq!(short.len(), 1);
+    }
+
+    #[test]
+    fn filters_skip_fixed_and_excluded_bodies() {
+        let mut physics = PhysicsStruct::empty((400.0, 400.0), Vector2::zeros());
+        let block = physics.create_fixed(
+            Vector2::new(100.0, 200.0),
+            ShapeHandle::new(Cuboid::new(Vector2::new(10.0, 10.0))),
+        );
+        let near = physics.create_ball(10.0, Vector2::new(200.0, 200.0), 1.0);
+        let far = physics.create_ball(10.0, Vector2::new(300.0, 200.0), 1.0);
+        physics.sync_geometry();
+        let first_hit = |filter: &QueryFilter| {
+            physics
+                .cast_ray(
+                    Vector2::new(0.0, 200.0),
+                    Vector2::new(1.0, 0.0),
+                    1000.0,
+                    filter,
+                )
+                .map(|hit| hit.body)
+        };
+
+        assert_eq!(first_hit(&QueryFilter::default()), Some(block));
+        let moving_only = QueryFilter {
+            skip_fixed: true,
+            ..QueryFilter::default()
+        };
+        assert_eq!(first_hit(&moving_only), Some(near));
+        assert!(physics
+            .bodies_at(Vector2::new(100.0, 200.0), &moving_only)
+            .is_empty());
+
+        let past_near = QueryFilter {
+            skip_fixed: true,
+            exclude: vec![near],
+            ..QueryFilter::default()
+        };
+        assert_eq!(first_hit(&past_near), Some(far));
+        assert_eq!(
+            physics.bodies_at(Vector2::new(200.0, 200.0), &QueryFilter::excluding(near)),
+            Vec::new()
+        );
+        assert_eq!(
+            physics.body_at(Vector2::new(300.0, 200.0), &QueryFilter::excluding(near)),
+            Some(far)
+        );
+    }
+
+    #[test]
+    fn queries_in_an_empty_world_find_nothing() {
+        let mut physics = PhysicsStruct::empty((400.0, 400.0), Vector2::zeros());
+        physics.sync_geometry();
+        let filter = QueryFilter {
+            sensors: true,
+            ..QueryFilter::default()
+        };
+
+        let origin = Vector2::new(200.0, 200.0);
+        assert_eq!(
+            physics.cast_ray(origin, Vector2::new(1.0, 0.0), 1000.0, &filter),
+            None
+        );
+        assert!(physics
+            .cast_ray_all(origin, Vector2::new(0.0, 1.0), 1000.0, &filter)
+            .is_empty());
+        assert!(physics.bodies_at(origin, &filter).is_empty());
+        assert_eq!(physics.body_at(origin, &filter), None);
+        assert!(physics
+            .bodies_in_aabb(Vector2::zeros(), Vector2::new(400.0, 400.0))
+            .is_empty());
+    }
+}
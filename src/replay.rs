@@ -0,0 +1,125 @@
+use crate::scene::Scene;
+use crate::timestep::DEFAULT_RATE;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+
+// Structures & enums
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedInput<E> {
+    pub tick: u64,
+    pub input: E,
+}
+
+/// Every input of a run along with the tick it was applied on. Together with
+/// the seed, scene and physics rate it was recorded against, this is enough
+/// to play the run back exactly.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InputLog<E> {
+    pub seed: u64,
+    pub scene: Scene,
+    #[serde(default = "default_rate")]
+    pub rate: u32,
+    pub inputs: Vec<RecordedInput<E>>,
+}
+
+pub struct Replay<E> {
+    pending: VecDeque<RecordedInput<E>>,
+}
+
+fn default_rate() -> u32 {
+    DEFAULT_RATE
+}
+
+impl<E> InputLog<E> {
+    pub fn new(seed: u64, scene: Scene, rate: u32) -> Self {
+        InputLog {
+            seed,
+            scene,
+            rate,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, input: E) {
+        self.inputs.push(RecordedInput { tick, input });
+    }
+
+    /// Forgets every input recorded on or after `tick`.
+    pub fn truncate_from(&mut self, tick: u64) {
+        self.inputs.retain(|recorded| recorded.tick < tick);
+    }
+}
+
+impl<E> Replay<E> {
+    pub fn new(log: InputLog<E>) -> Self {
+        let mut pending: Vec<RecordedInput<E>> = log.inputs;
+        pending.sort_by_key(|recorded| recorded.tick);
+
+        Replay {
+            pending: pending.into(),
+        }
+    }
+
+    /// Removes and returns every input due on or before `tick`, oldest first.
+    pub fn take_due(&mut self, tick: u64) -> Vec<E> {
+        let mut due = Vec::new();
+        while matches!(self.pending.front(), Some(recorded) if recorded.tick <= tick) {
+            if let Some(recorded) = self.pending.pop_front() {
+                due.push(recorded.input);
+            }
+        }
+        due
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(inputs: &[(u64, &'static str)]) -> InputLog<&'static str> {
+        let mut log = InputLog::new(1, Scene::top_down(), DEFAULT_RATE);
+        for &(tick, input) in inputs {
+            log.record(tick, input);
+        }
+        log
+    }
+
+    #[test]
+    fn take_due_returns_inputs_in_tick_order() {
+        let mut replay = Replay::new(log(&[(5, "c"), (1, "a"), (3, "b")]));
+        assert_eq!(replay.take_due(0), Vec::<&str>::new());
+        assert_eq!(replay.take_due(3), vec!["a", "b"]);
+        assert_eq!(replay.take_due(4), Vec::<&str>::new());
+        assert!(!replay.is_finished());
+        assert_eq!(replay.take_due(5), vec!["c"]);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn inputs_on_the_same_tick_keep_their_recorded_order() {
+        let mut replay = Replay::new(log(&[(2, "first"), (1, "early"), (2, "second")]));
+        assert_eq!(replay.take_due(2), vec!["early", "first", "second"]);
+    }
+
+    #[test]
+    fn late_calls_catch_up_on_everything_missed() {
+        let mut replay = Replay::new(log(&[(1, "a"), (2, "b"), (10, "c")]));
+        assert_eq!(replay.take_due(100), vec!["a", "b", "c"]);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn truncate_from_drops_that_tick_and_later() {
+        let mut log = log(&[(1, "a"), (2, "b"), (3, "c")]);
+        log.truncate_from(2);
+        let ticks: Vec<u64> = log.inputs.iter().map(|recorded| recorded.tick).collect();
+        assert_eq!(ticks, vec![1]);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::physics::PhysicsStruct;
+
+use nalgebra::Isometry2;
+use nphysics2d::math::Velocity;
+use nphysics2d::object::DefaultBodyHandle;
+
+use std::collections::VecDeque;
+
+// Structures & enums
+
+/// Position and velocity of every rigid body at one tick, plus whatever else
+/// the caller needs to put back (floater targets, for example). Contacts
+/// aren't kept, so stepping on from a restored frame can differ slightly from
+/// the original run.
+pub struct Frame<T> {
+    pub tick: u64,
+    bodies: Vec<(DefaultBodyHandle, Isometry2<f32>, Velocity<f32>)>,
+    pub extra: T,
+}
+
+/// The most recent frames of a run, oldest first. While the cursor is set the
+/// run is rewound to that frame; recording again drops every frame after it,
+/// so resuming branches a new history.
+pub struct RewindBuffer<T> {
+    frames: VecDeque<Frame<T>>,
+    capacity: usize,
+    cursor: Option<usize>,
+}
+
+impl<T> Frame<T> {
+    pub fn capture(tick: u64, physics: &PhysicsStruct, extra: T) -> Self {
+        let bodies = physics
+            .bodies
+            .iter()
+            .filter_map(|(handle, _)| {
+                let (position, velocity) = physics.try_get_state_of(handle).ok()?;
+                Some((handle, position, velocity))
+            })
+            .collect();
+
+        Frame {
+            tick,
+            bodies,
+            extra,
+        }
+    }
+
+    pub fn restore(&self, physics: &mut PhysicsStruct) {
+        for &(handle, position, velocity) in self.bodies.iter() {
+            // Bodies removed since the frame was taken stay removed.
+            physics.try_set_state_of(handle, position, velocity).ok();
+        }
+        physics.sync_geometry();
+    }
+}
+
+impl<T> RewindBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            cursor: None,
+        }
+    }
+
+    /// Adds a frame after the current one, dropping the oldest frame once the
+    /// buffer is full.
+    pub fn record(&mut self, tick: u64, physics: &PhysicsStruct, extra: T) {
+        if let Some(cursor) = self.cursor.take() {
+            self.frames.truncate(cursor + 1);
+        }
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(Frame::capture(tick, physics, extra));
+    }
+
+    /// Moves the cursor `count` frames back, stopping at the oldest one.
+    pub fn step_back(&mut self, count: usize) -> Option<&Frame<T>> {
+        let current = self.cursor.or_else(|| self.frames.len().checked_sub(1))?;
+        self.seek(current.saturating_sub(count))
+    }
+
+    /// Moves the cursor to `index`, counted from the oldest frame.
+    pub fn seek(&mut self, index: usize) -> Option<&Frame<T>> {
+        let index = index.min(self.frames.len().checked_sub(1)?);
+        self.cursor = Some(index);
+        self.frames.get(index)
+    }
+
+    /// The frame the run is rewound to, if any.
+    pub fn cursor(&self) -> Option<usize> {
+        self.cursor
+    }
+
+    pub fn is_rewound(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.cursor = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::Vector2;
+
+    fn filled(capacity: usize, ticks: u64) -> (RewindBuffer<()>, PhysicsStruct) {
+        let physics = PhysicsStruct::new((100.0, 100.0));
+        let mut buffer = RewindBuffer::new(capacity);
+        for tick in 0..ticks {
+            buffer.record(tick, &physics, ());
+        }
+        (buffer, physics)
+    }
+
+    #[test]
+    fn oldest_frames_are_dropped_at_capacity() {
+        let (mut buffer, _) = filled(3, 5);
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.seek(0).map(|frame| frame.tick), Some(2));
+    }
+
+    #[test]
+    fn cursor_moves_back_and_stops_at_the_ends() {
+        let (mut buffer, _) = filled(10, 5);
+        assert!(!buffer.is_rewound());
+        assert_eq!(buffer.step_back(1).map(|frame| frame.tick), Some(3));
+        assert_eq!(buffer.step_back(2).map(|frame| frame.tick), Some(1));
+        assert_eq!(buffer.step_back(10).map(|frame| frame.tick), Some(0));
+        assert_eq!(buffer.seek(100).map(|frame| frame.tick), Some(4));
+        assert_eq!(buffer.cursor(), Some(4));
+    }
+
+    #[test]
+    fn recording_while_rewound_drops_the_frames_after_the_cursor() {
+        let (mut buffer, physics) = filled(10, 5);
+        buffer.seek(1);
+        buffer.record(2, &physics, ());
+        assert!(!buffer.is_rewound());
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.step_back(0).map(|frame| frame.tick), Some(2));
+    }
+
+    #[test]
+    fn an_empty_buffer_has_nothing_to_seek() {
+        let mut buffer: RewindBuffer<()> = RewindBuffer::new(0);
+        assert_eq!(buffer.capacity(), 1);
+        assert!(buffer.step_back(1).is_none());
+        assert!(buffer.seek(0).is_none());
+        assert!(!buffer.is_rewound());
+    }
+
+    #[test]
+    fn restoring_puts_bodies_back() {
+        let mut physics = PhysicsStruct::new((100.0, 100.0));
+        let ball = physics.create_ball(5.0, Vector2::new(50.0, 50.0), 0.01);
+        let frame = Frame::capture(0, &physics, ());
+        let (position, _) = physics.get_state_of(ball);
+        physics.set_state_of(ball, position, Velocity::linear(100.0, 0.0));
+        for _ in 0..10 {
+            physics.step();
+        }
+        assert!(physics.get_pos_of(ball) != Vector2::new(50.0, 50.0));
+
+        frame.restore(&mut physics);
+        assert_eq!(physics.get_pos_of(ball), Vector2::new(50.0, 50.0));
+        assert_eq!(physics.get_vel_of(ball), Vector2::zeros());
+    }
+}
@@ -0,0 +1,420 @@
+use crate::arrival::ArrivalSettings;
+use crate::soccer::MatchSettings;
+use crate::zones::ZoneDesc;
+
+use ggez::graphics::Color;
+use nphysics2d::object::DefaultBodyHandle;
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const TOP_DOWN_SCENE: &str = include_str!("../scenes/top_down.ron");
+const SIDE_VIEW_SCENE: &str = include_str!("../scenes/side_view.ron");
+const SOCCER_SCENE: &str = include_str!("../scenes/soccer.ron");
+
+// Structures & enums
+
+/// A declarative description of a world, usually loaded from a RON file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Scene {
+    pub arena: Arena,
+    #[serde(default)]
+    pub gravity: (f32, f32),
+    pub bodies: Vec<BodyDesc>,
+    /// Makes a top_down scene a soccer match: the floaters split into two
+    /// teams and goals go at each end.
+    #[serde(default)]
+    pub soccer: Option<MatchSettings>,
+    /// Trigger zones, in top_down scenes.
+    #[serde(default)]
+    pub zones: Vec<ZoneDesc>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    /// Whether a wall runs along the top. Side views leave it off so bodies
+    /// can be thrown up out of sight.
+    #[serde(default = "default_ceiling")]
+    pub ceiling: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Role {
+    Floater,
+    BeachBall,
+    Player,
+    Prop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ShapeDesc {
+    Ball { radius: f32 },
+    Cuboid { half_width: f32, half_height: f32 },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Placement {
+    At(f32, f32),
+    Random,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BodyDesc {
+    pub role: Role,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    pub shape: ShapeDesc,
+    pub density: f32,
+    #[serde(default)]
+    pub linear_damping: f32,
+    #[serde(default)]
+    pub color: Option<(f32, f32, f32, f32)>,
+    pub position: Placement,
+    #[serde(default)]
+    pub velocity: (f32, f32),
+    #[serde(default)]
+    pub fixed: bool,
+    /// How floaters steer to their targets; the defaults if left out.
+    #[serde(default)]
+    pub arrival: Option<ArrivalSettings>,
+}
+
+/// A body created while loading a scene, with the parts of its description
+/// that the game needs for drawing.
+#[derive(Clone, Copy, Debug)]
+pub struct SceneBody {
+    pub handle: DefaultBodyHandle,
+    pub role: Role,
+    pub shape: ShapeDesc,
+    pub color: Option<Color>,
+    pub arrival: Option<ArrivalSettings>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(ron::Error),
+    Invalid { body: Option<usize>, reason: String },
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_ceiling() -> bool {
+    true
+}
+
+// Also rejects NaN, which plain `<= 0.0` checks would let through.
+pub(crate) fn is_positive(value: f32) -> bool {
+    value > 0.0
+}
+
+impl ShapeDesc {
+    /// Radius of the smallest circle around the shape.
+    pub fn bounding_radius(&self) -> f32 {
+        match *self {
+            ShapeDesc::Ball { radius } => radius,
+            ShapeDesc::Cuboid {
+                half_width,
+                half_height,
+            } => (half_width * half_width + half_height * half_height).sqrt(),
+        }
+    }
+}
+
+impl Scene {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let text = fs::read_to_string(path).map_err(SceneError::Io)?;
+        Scene::from_ron(&text)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Scene, SceneError> {
+        let scene: Scene = ron::de::from_str(text).map_err(SceneError::Parse)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn top_down() -> Scene {
+        Scene::from_ron(TOP_DOWN_SCENE).expect("Built-in top_down scene is invalid")
+    }
+
+    pub fn side_view() -> Scene {
+        Scene::from_ron(SIDE_VIEW_SCENE).expect("Built-in side_view scene is invalid")
+    }
+
+    pub fn soccer() -> Scene {
+        Scene::from_ron(SOCCER_SCENE).expect("Built-in soccer scene is invalid")
+    }
+
+    pub fn validate(&self) -> Result<(), SceneError> {
+        let arena = self.arena;
+        if !is_positive(arena.width) || !is_positive(arena.height) {
+            return Err(SceneError::invalid(
+                None,
+                format!(
+                    "arena must have a positive size, got {} x {}",
+                    arena.width, arena.height
+                ),
+            ));
+        }
+        if !(self.gravity.0.is_finite() && self.gravity.1.is_finite()) {
+            return Err(SceneError::invalid(
+                None,
+                "gravity must be finite".to_string(),
+            ));
+        }
+
+        for (index, body) in self.bodies.iter().enumerate() {
+            body.validate(arena)
+                .map_err(|reason| SceneError::invalid(Some(index), reason))?;
+        }
+
+        for zone in self.zones.iter() {
+            zone.validate()
+                .map_err(|reason| SceneError::invalid(None, reason))?;
+        }
+
+        if let Some(soccer) = self.soccer {
+            soccer
+                .validate((arena.width, arena.height))
+                .map_err(|reason| SceneError::invalid(None, reason))?;
+            let floaters = self.bodies_with_role(Role::Floater);
+            if floaters == 0 || floaters % 2 != 0 {
+                return Err(SceneError::invalid(
+                    None,
+                    format!(
+                        "soccer needs an even number of floaters to make two teams, got {}",
+                        floaters
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn bodies_with_role(&self, role: Role) -> u32 {
+        self.bodies
+            .iter()
+            .filter(|body| body.role == role)
+            .map(|body| body.count)
+            .sum()
+    }
+}
+
+impl BodyDesc {
+    pub fn color(&self) -> Option<Color> {
+        self.color.map(|(r, g, b, a)| Color::new(r, g, b, a))
+    }
+
+    fn validate(&self, arena: Arena) -> Result<(), String> {
+        if self.count == 0 {
+            return Err("count must be at least 1".to_string());
+        }
+        if self.count > 1 {
+            if let Placement::At(..) = self.position {
+                return Err("bodies with a count above 1 need a Random position".to_string());
+            }
+        }
+
+        match self.shape {
+            ShapeDesc::Ball { radius } if !is_positive(radius) => {
+                return Err(format!("radius must be positive, got {}", radius));
+            }
+            ShapeDesc::Cuboid {
+                half_width,
+                half_height,
+            } if !is_positive(half_width) || !is_positive(half_height) => {
+                return Err(format!(
+                    "cuboid half extents must be positive, got {} x {}",
+                    half_width, half_height
+                ));
+            }
+            _ => (),
+        }
+
+        match (self.role, self.shape) {
+            (Role::Floater, ShapeDesc::Ball { .. })
+            | (Role::BeachBall, ShapeDesc::Ball { .. })
+            | (Role::Player, ShapeDesc::Ball { .. })
+            | (Role::Prop, _) => (),
+            (role, _) => return Err(format!("{:?} bodies must use a Ball shape", role)),
+        }
+
+        if let Some(arrival) = self.arrival {
+            if self.role != Role::Floater {
+                return Err("only Floater bodies can have arrival settings".to_string());
+            }
+            arrival.validate()?;
+        }
+
+        if self.fixed && self.role != Role::Prop {
+            return Err("only Prop bodies can be fixed".to_string());
+        }
+        if !self.fixed && !is_positive(self.density) {
+            return Err(format!("density must be positive, got {}", self.density));
+        }
+        if self.linear_damping.is_nan() || self.linear_damping < 0.0 {
+            return Err(format!(
+                "linear_damping can't be negative, got {}",
+                self.linear_damping
+            ));
+        }
+
+        if let Some((r, g, b, a)) = self.color {
+            if [r, g, b, a].iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return Err(format!(
+                    "color components must be between 0 and 1, got ({}, {}, {}, {})",
+                    r, g, b, a
+                ));
+            }
+        }
+
+        if let Placement::At(x, y) = self.position {
+            if !(x >= 0.0 && x <= arena.width && y >= 0.0 && y <= arena.height) {
+                return Err(format!("position ({}, {}) is outside the arena", x, y));
+            }
+        }
+        if self.shape.bounding_radius() * 2.0 >= arena.width.min(arena.height) {
+            return Err("body is too large to fit in the arena".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl SceneError {
+    pub fn invalid(body: Option<usize>, reason: String) -> Self {
+        SceneError::Invalid { body, reason }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "could not read scene: {}", e),
+            SceneError::Parse(e) => write!(f, "could not parse scene: {}", e),
+            SceneError::Invalid {
+                body: Some(index),
+                reason,
+            } => write!(f, "invalid scene: body {}: {}", index, reason),
+            SceneError::Invalid { body: None, reason } => {
+                write!(f, "invalid scene: {}", reason)
+            }
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io(e) => Some(e),
+            SceneError::Parse(e) => Some(e),
+            SceneError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejects(scene: &Scene) -> bool {
+        match scene.validate() {
+            Err(SceneError::Invalid { .. }) => true,
+            Err(e) => panic!("expected an invalid scene, got {}", e),
+            Ok(()) => false,
+        }
+    }
+
+    fn with_body(change: impl Fn(&mut BodyDesc)) -> Scene {
+        let mut scene = Scene::top_down();
+        change(&mut scene.bodies[0]);
+        scene
+    }
+
+    #[test]
+    fn built_in_scenes_are_valid() {
+        for scene in [Scene::top_down(), Scene::side_view(), Scene::soccer()].iter() {
+            assert!(scene.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn zones_demo_scene_loads() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/zones_demo.ron");
+        let scene = Scene::from_file(path).expect("zones_demo.ron is valid");
+        assert_eq!(scene.zones.len(), 2);
+    }
+
+    #[test]
+    fn arena_must_have_a_positive_size() {
+        for &size in [0.0, -100.0, f32::NAN].iter() {
+            let mut scene = Scene::top_down();
+            scene.arena.width = size;
+            assert!(rejects(&scene), "width {}", size);
+
+            let mut scene = Scene::top_down();
+            scene.arena.height = size;
+            assert!(rejects(&scene), "height {}", size);
+        }
+    }
+
+    #[test]
+    fn gravity_must_be_finite() {
+        for &gravity in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY].iter() {
+            let mut scene = Scene::top_down();
+            scene.gravity = (0.0, gravity);
+            assert!(rejects(&scene), "gravity {}", gravity);
+        }
+    }
+
+    #[test]
+    fn shapes_must_have_a_positive_size() {
+        for &size in [0.0, -1.0, f32::NAN].iter() {
+            let scene = with_body(|body| body.shape = ShapeDesc::Ball { radius: size });
+            assert!(rejects(&scene), "radius {}", size);
+        }
+
+        let mut scene = Scene::top_down();
+        scene.bodies[0].role = Role::Prop;
+        scene.bodies[0].shape = ShapeDesc::Cuboid {
+            half_width: 10.0,
+            half_height: f32::NAN,
+        };
+        assert!(rejects(&scene));
+    }
+
+    #[test]
+    fn density_must_be_positive_unless_fixed() {
+        for &density in [0.0, -0.1, f32::NAN].iter() {
+            let scene = with_body(|body| body.density = density);
+            assert!(rejects(&scene), "density {}", density);
+        }
+    }
+
+    #[test]
+    fn damping_cant_be_negative_or_nan() {
+        for &damping in [-1.0, f32::NAN].iter() {
+            let scene = with_body(|body| body.linear_damping = damping);
+            assert!(rejects(&scene), "damping {}", damping);
+        }
+    }
+
+    #[test]
+    fn errors_name_the_body() {
+        let mut scene = Scene::top_down();
+        scene.bodies[1].density = 0.0;
+        match scene.validate() {
+            Err(SceneError::Invalid { body, .. }) => assert_eq!(body, Some(1)),
+            other => panic!("expected an invalid body, got {:?}", other),
+        }
+    }
+}
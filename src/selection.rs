@@ -0,0 +1,201 @@
+use nphysics2d::object::DefaultBodyHandle;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashSet;
+
+// Constants
+pub const CONTROL_GROUPS: usize = 10;
+
+// Structures & enums
+
+/// How a click or box selection combines with what's already selected.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SelectMode {
+    Replace,
+    Add,
+    Toggle,
+}
+
+impl SelectMode {
+    /// Shift adds and Ctrl toggles; Ctrl wins if both are held.
+    pub fn from_modifiers(shift: bool, ctrl: bool) -> Self {
+        if ctrl {
+            SelectMode::Toggle
+        } else if shift {
+            SelectMode::Add
+        } else {
+            SelectMode::Replace
+        }
+    }
+
+    pub fn apply(self, selected: &mut HashSet<DefaultBodyHandle>, hits: &[DefaultBodyHandle]) {
+        match self {
+            SelectMode::Replace => {
+                selected.clear();
+                selected.extend(hits);
+            }
+            SelectMode::Add => selected.extend(hits),
+            SelectMode::Toggle => {
+                for handle in hits {
+                    if !selected.remove(handle) {
+                        selected.insert(*handle);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The selected floaters plus RTS-style control groups, numbered 0 to 9.
+#[derive(Clone)]
+pub struct Selection {
+    pub selected: HashSet<DefaultBodyHandle>,
+    groups: Vec<Vec<DefaultBodyHandle>>,
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Selection {
+            selected: HashSet::new(),
+            groups: vec![Vec::new(); CONTROL_GROUPS],
+        }
+    }
+
+    pub fn select(&mut self, mode: SelectMode, hits: &[DefaultBodyHandle]) {
+        mode.apply(&mut self.selected, hits);
+    }
+
+    pub fn contains(&self, handle: DefaultBodyHandle) -> bool {
+        self.selected.contains(&handle)
+    }
+
+    /// Makes `group` hold exactly the current selection.
+    pub fn assign_group(&mut self, group: usize) {
+        if let Some(members) = self.groups.get_mut(group) {
+            *members = self.selected.iter().copied().collect();
+        }
+    }
+
+    /// Selects `group` in place of the current selection. An empty group
+    /// leaves the selection alone.
+    pub fn recall_group(&mut self, group: usize) {
+        if let Some(members) = self.groups.get(group) {
+            if !members.is_empty() {
+                self.selected = members.iter().copied().collect();
+            }
+        }
+    }
+
+    pub fn group(&self, group: usize) -> &[DefaultBodyHandle] {
+        self.groups.get(group).map_or(&[], |members| &members[..])
+    }
+
+    pub fn set_group(&mut self, group: usize, members: Vec<DefaultBodyHandle>) {
+        if let Some(slot) = self.groups.get_mut(group) {
+            *slot = members;
+        }
+    }
+
+    /// The lowest numbered group `handle` belongs to, for badges.
+    pub fn group_of(&self, handle: DefaultBodyHandle) -> Option<usize> {
+        self.groups
+            .iter()
+            .position(|members| members.contains(&handle))
+    }
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nphysics2d::object::{DefaultBodySet, Ground};
+
+    fn handles(count: usize) -> Vec<DefaultBodyHandle> {
+        let mut bodies = DefaultBodySet::<f32>::new();
+        (0..count).map(|_| bodies.insert(Ground::new())).collect()
+    }
+
+    fn selected(selection: &Selection) -> HashSet<DefaultBodyHandle> {
+        selection.selected.clone()
+    }
+
+    #[test]
+    fn modifiers_pick_the_mode() {
+        assert_eq!(
+            SelectMode::from_modifiers(false, false),
+            SelectMode::Replace
+        );
+        assert_eq!(SelectMode::from_modifiers(true, false), SelectMode::Add);
+        assert_eq!(SelectMode::from_modifiers(false, true), SelectMode::Toggle);
+        assert_eq!(SelectMode::from_modifiers(true, true), SelectMode::Toggle);
+    }
+
+    #[test]
+    fn replace_add_and_toggle() {
+        let h = handles(3);
+        let mut selection = Selection::new();
+        selection.select(SelectMode::Replace, &h[..2]);
+        assert_eq!(selected(&selection), h[..2].iter().copied().collect());
+
+        selection.select(SelectMode::Replace, &h[2..]);
+        assert_eq!(selected(&selection), h[2..].iter().copied().collect());
+
+        selection.select(SelectMode::Add, &h[..1]);
+        assert_eq!(selected(&selection), [h[0], h[2]].iter().copied().collect());
+
+        selection.select(SelectMode::Toggle, &h[..2]);
+        assert_eq!(selected(&selection), [h[1], h[2]].iter().copied().collect());
+    }
+
+    #[test]
+    fn replacing_with_nothing_clears() {
+        let h = handles(2);
+        let mut selection = Selection::new();
+        selection.select(SelectMode::Replace, &h);
+        selection.select(SelectMode::Replace, &[]);
+        assert!(selection.selected.is_empty());
+    }
+
+    #[test]
+    fn groups_are_assigned_and_recalled() {
+        let h = handles(3);
+        let mut selection = Selection::new();
+        selection.select(SelectMode::Replace, &h[..2]);
+        selection.assign_group(1);
+        selection.select(SelectMode::Replace, &h[2..]);
+        assert_eq!(selection.group_of(h[0]), Some(1));
+        assert_eq!(selection.group_of(h[2]), None);
+
+        selection.recall_group(1);
+        assert_eq!(selected(&selection), h[..2].iter().copied().collect());
+    }
+
+    #[test]
+    fn reassigning_a_group_replaces_it() {
+        let h = handles(2);
+        let mut selection = Selection::new();
+        selection.select(SelectMode::Replace, &h[..1]);
+        selection.assign_group(3);
+        selection.select(SelectMode::Replace, &h[1..]);
+        selection.assign_group(3);
+        assert_eq!(selection.group(3), &h[1..]);
+    }
+
+    #[test]
+    fn empty_or_missing_groups_leave_the_selection_alone() {
+        let h = handles(1);
+        let mut selection = Selection::new();
+        selection.select(SelectMode::Replace, &h);
+        selection.recall_group(0);
+        selection.recall_group(CONTROL_GROUPS);
+        selection.assign_group(CONTROL_GROUPS);
+        assert!(selection.contains(h[0]));
+        assert!(selection.group(CONTROL_GROUPS).is_empty());
+    }
+}
@@ -0,0 +1,317 @@
+use crate::avoidance::AvoidanceSettings;
+use crate::scene::{Role, ShapeDesc};
+use crate::soccer::{MatchPhase, MatchSettings, Team};
+use crate::steering::ControllerKind;
+use crate::zones::ZoneDesc;
+
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Structures & enums
+
+/// Everything needed to rebuild a `PhysicsStruct`. Bodies are stored in
+/// iteration order and referred to by their index in `bodies`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhysicsSnapshot {
+    pub dims: (f32, f32),
+    pub gravity: (f32, f32),
+    #[serde(default = "default_timestep")]
+    pub timestep: f32,
+    pub bodies: Vec<BodySnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BodySnapshot {
+    pub kind: BodyKind,
+    pub colliders: Vec<ColliderSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BodyKind {
+    Ground,
+    Rigid(RigidBodyState),
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RigidBodyState {
+    pub position: (f32, f32),
+    pub rotation: f32,
+    pub linear_velocity: (f32, f32),
+    pub angular_velocity: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+}
+
+/// A collider, positioned relative to the body it is attached to.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ColliderSnapshot {
+    pub shape: ShapeDesc,
+    pub position: (f32, f32),
+    pub rotation: f32,
+    pub density: f32,
+    pub sensor: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopDownSnapshot {
+    pub physics: PhysicsSnapshot,
+    pub floaters: Vec<FloaterSnapshot>,
+    pub beach_ball: BallSnapshot,
+    pub props: Vec<PropSnapshot>,
+    /// Indices into `floaters`.
+    pub selected: Vec<usize>,
+    #[serde(default)]
+    pub tick: u64,
+    /// Control groups, each a list of indices into `floaters`.
+    #[serde(default)]
+    pub groups: Vec<Vec<usize>>,
+    #[serde(default)]
+    pub formations: Vec<FormationSnapshot>,
+    /// Avoidance settings, if it was switched on.
+    #[serde(default)]
+    pub avoidance: Option<AvoidanceSettings>,
+    #[serde(default)]
+    pub soccer: Option<MatchSnapshot>,
+    #[serde(default)]
+    pub zones: Vec<ZoneSnapshot>,
+}
+
+/// A soccer match in progress. Goal sensors are indices into
+/// `PhysicsSnapshot::bodies`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MatchSnapshot {
+    pub settings: MatchSettings,
+    pub goals: Vec<GoalSnapshot>,
+    pub score: [u32; 2],
+    pub time_left: f32,
+    pub phase: MatchPhase,
+    pub last_scorer: Option<Team>,
+    #[serde(default = "default_kicker")]
+    pub kicker: Team,
+    #[serde(default)]
+    pub kickoffs: u32,
+    #[serde(default)]
+    pub kickoff_seed: u64,
+    #[serde(default)]
+    pub stalled: f32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct GoalSnapshot {
+    pub defender: Team,
+    pub sensor: usize,
+    pub center: (f32, f32),
+    pub half_extents: (f32, f32),
+}
+
+/// A trigger zone. The sensor and the bodies are indices into
+/// `PhysicsSnapshot::bodies`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ZoneSnapshot {
+    pub desc: ZoneDesc,
+    pub sensor: usize,
+    pub watch: Option<Vec<usize>>,
+    pub inside: Vec<usize>,
+}
+
+/// A formation move in progress. Members are indices into `floaters` with
+/// their slot offsets.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FormationSnapshot {
+    pub members: Vec<(usize, (f32, f32))>,
+    pub anchor: (f32, f32),
+    pub destination: (f32, f32),
+    pub speed: f32,
+    pub slack: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FloaterSnapshot {
+    pub body: usize,
+    pub radius: f32,
+    pub color: [f32; 4],
+    pub target: Option<(f32, f32)>,
+    #[serde(default)]
+    pub waypoints: Vec<(f32, f32)>,
+    #[serde(default)]
+    pub patrol: bool,
+    #[serde(default)]
+    pub hold: bool,
+    #[serde(default)]
+    pub controller: ControllerKind,
+    #[serde(default)]
+    pub team: Option<Team>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct BallSnapshot {
+    pub body: usize,
+    pub radius: f32,
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PropSnapshot {
+    pub body: usize,
+    pub role: Role,
+    pub shape: ShapeDesc,
+    pub color: Option<[f32; 4]>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Ron(ron::Error),
+    Invalid(String),
+}
+
+fn default_timestep() -> f32 {
+    1.0 / 60.0
+}
+
+fn default_kicker() -> Team {
+    Team::Green
+}
+
+pub fn save<T: Serialize, P: AsRef<Path>>(path: P, snapshot: &T) -> Result<(), SnapshotError> {
+    let text = ron::ser::to_string_pretty(snapshot, PrettyConfig::default())
+        .map_err(SnapshotError::Ron)?;
+    fs::write(path, text).map_err(SnapshotError::Io)
+}
+
+pub fn load<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, SnapshotError> {
+    let text = fs::read_to_string(path).map_err(SnapshotError::Io)?;
+    ron::de::from_str(&text).map_err(SnapshotError::Ron)
+}
+
+impl PhysicsSnapshot {
+    pub fn check_body(&self, index: usize) -> Result<usize, SnapshotError> {
+        if index < self.bodies.len() {
+            Ok(index)
+        } else {
+            Err(SnapshotError::Invalid(format!(
+                "body {} does not exist, the snapshot only has {} bodies",
+                index,
+                self.bodies.len()
+            )))
+        }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot file error: {}", e),
+            SnapshotError::Ron(e) => write!(f, "snapshot format error: {}", e),
+            SnapshotError::Invalid(reason) => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            SnapshotError::Ron(e) => Some(e),
+            SnapshotError::Invalid(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::floater::PhysicsBall;
+    use crate::scene::Scene;
+    use crate::selection::{SelectMode, Selection};
+    use crate::world::TopDownWorld;
+
+    use nalgebra::Vector2;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn top_down_snapshot_survives_save_and_load() {
+        let mut world =
+            TopDownWorld::from_scene(&Scene::top_down(), &mut StdRng::seed_from_u64(7)).unwrap();
+        world.floaters[0].set_target(Vector2::new(400.0, 300.0));
+        for _ in 0..30 {
+            world.update();
+        }
+        let mut selection = Selection::new();
+        let picked: Vec<_> = world.floaters[..3]
+            .iter()
+            .map(|floater| floater.get_handle())
+            .collect();
+        selection.select(SelectMode::Replace, &picked);
+        selection.assign_group(2);
+
+        let snapshot = world.snapshot(&selection).unwrap();
+        let path = env::temp_dir().join(format!("top_down_snapshot_{}.ron", process::id()));
+        save(&path, &snapshot).unwrap();
+        let loaded: TopDownSnapshot = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let (restored, restored_selection) = TopDownWorld::from_snapshot(&loaded).unwrap();
+        assert_eq!(restored.tick, world.tick);
+        assert_eq!(restored.floaters.len(), world.floaters.len());
+        let pairs = world
+            .floaters
+            .iter()
+            .map(|floater| floater.get_handle())
+            .zip(restored.floaters.iter().map(|floater| floater.get_handle()))
+            .chain(std::iter::once((
+                world.beach_ball.get_handle(),
+                restored.beach_ball.get_handle(),
+            )));
+        for (before, after) in pairs {
+            assert_eq!(
+                world.physics.get_pos_of(before),
+                restored.physics.get_pos_of(after)
+            );
+            assert_eq!(
+                world.physics.get_vel_of(before),
+                restored.physics.get_vel_of(after)
+            );
+        }
+        for (index, floater) in restored.floaters.iter().enumerate() {
+            assert_eq!(restored_selection.contains(floater.get_handle()), index < 3);
+        }
+        assert_eq!(restored_selection.group(2).len(), 3);
+        assert_eq!(
+            restored.floaters[0].target,
+            Some(Vector2::new(400.0, 300.0))
+        );
+    }
+
+    #[test]
+    fn loading_a_missing_file_is_an_io_error() {
+        let path = env::temp_dir().join("no_such_snapshot.ron");
+        match load::<TopDownSnapshot, _>(&path) {
+            Err(SnapshotError::Io(_)) => (),
+            other => panic!("expected an io error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn out_of_range_body_indices_are_invalid() {
+        let world =
+            TopDownWorld::from_scene(&Scene::top_down(), &mut StdRng::seed_from_u64(7)).unwrap();
+        let mut snapshot = world.snapshot(&Selection::new()).unwrap();
+        snapshot.beach_ball.body = snapshot.physics.bodies.len();
+        assert!(matches!(
+            TopDownWorld::from_snapshot(&snapshot),
+            Err(SnapshotError::Invalid(_))
+        ));
+    }
+}
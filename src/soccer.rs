@@ -0,0 +1,371 @@
+use crate::floater::{GREEN, RED};
+use crate::team_ai::Difficulty;
+
+use ggez::graphics::Color;
+use nalgebra::Vector2;
+use nphysics2d::object::DefaultBodyHandle;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+
+// Constants
+/// Slowest the ball can move and still count as in play, in pixels a second.
+const STALL_SPEED: f32 = 5.0;
+/// Seconds the ball can sit still before play restarts with a kickoff.
+const STALL_TIME: f32 = 5.0;
+
+// Structures & enums
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Team {
+    /// Defends the left goal.
+    Green,
+    /// Defends the right goal.
+    Red,
+}
+
+/// Rules for a match, set in the scene file's `soccer` section.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchSettings {
+    /// Length of the match in seconds of play, not counting kickoffs.
+    pub duration: f32,
+    /// Height of each goal mouth.
+    pub goal_width: f32,
+    /// How far each goal reaches into the arena from its end wall.
+    pub goal_depth: f32,
+    /// Seconds everyone stands still before play restarts.
+    pub kickoff_delay: f32,
+    /// Hands Green to the computer at this difficulty.
+    pub green_ai: Option<Difficulty>,
+    /// Hands Red to the computer at this difficulty.
+    pub red_ai: Option<Difficulty>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MatchPhase {
+    /// Lined up and waiting for the whistle, in seconds.
+    Kickoff {
+        remaining: f32,
+    },
+    Playing,
+    Finished,
+}
+
+/// A goal mouth: a sensor that scores for the other team when the beach
+/// ball gets into it.
+#[derive(Clone, Copy, Debug)]
+pub struct Goal {
+    pub defender: Team,
+    pub sensor: DefaultBodyHandle,
+    pub center: Vector2<f32>,
+    pub half_extents: Vector2<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub settings: MatchSettings,
+    pub goals: Vec<Goal>,
+    /// Goals for Green and Red, in that order.
+    pub score: [u32; 2],
+    /// Seconds of play left.
+    pub time_left: f32,
+    pub phase: MatchPhase,
+    pub last_scorer: Option<Team>,
+    /// Who has the ball at the next kickoff.
+    pub kicker: Team,
+    /// Kickoffs so far, restarts included.
+    pub kickoffs: u32,
+    /// Picks who kicks off first and nudges everyone's kickoff spots, so
+    /// matches with different seeds don't all replay the same opening.
+    pub kickoff_seed: u64,
+    /// Seconds of play the ball has sat still for.
+    pub stalled: f32,
+}
+
+impl Team {
+    pub fn color(self) -> Color {
+        match self {
+            Team::Green => GREEN,
+            Team::Red => RED,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Team::Green => "Green",
+            Team::Red => "Red",
+        }
+    }
+
+    pub fn opponent(self) -> Team {
+        match self {
+            Team::Green => Team::Red,
+            Team::Red => Team::Green,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            Team::Green => 0,
+            Team::Red => 1,
+        }
+    }
+
+    /// Which way this team attacks along x: +1 toward the right goal.
+    pub fn attack_direction(self) -> f32 {
+        match self {
+            Team::Green => 1.0,
+            Team::Red => -1.0,
+        }
+    }
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        MatchSettings {
+            duration: 120.0,
+            goal_width: 200.0,
+            goal_depth: 30.0,
+            kickoff_delay: 1.5,
+            green_ai: None,
+            red_ai: None,
+        }
+    }
+}
+
+impl MatchSettings {
+    pub fn validate(&self, arena: (f32, f32)) -> Result<(), String> {
+        if self.duration.is_nan() || self.duration <= 0.0 {
+            return Err(format!("duration must be positive, got {}", self.duration));
+        }
+        if self.kickoff_delay.is_nan() || self.kickoff_delay < 0.0 {
+            return Err(format!(
+                "kickoff_delay can't be negative, got {}",
+                self.kickoff_delay
+            ));
+        }
+        if !(self.goal_width > 0.0 && self.goal_width <= arena.1) {
+            return Err(format!(
+                "goal_width must be positive and fit the arena, got {}",
+                self.goal_width
+            ));
+        }
+        if !(self.goal_depth > 0.0 && self.goal_depth < arena.0 / 4.0) {
+            return Err(format!(
+                "goal_depth must be positive and under a quarter of the arena, got {}",
+                self.goal_depth
+            ));
+        }
+        Ok(())
+    }
+
+    /// The difficulty the computer plays `team` at, if it does.
+    pub fn ai_for(&self, team: Team) -> Option<Difficulty> {
+        match team {
+            Team::Green => self.green_ai,
+            Team::Red => self.red_ai,
+        }
+    }
+
+    /// Where the goals go in an arena of `dims`: the centre and half
+    /// extents of the left goal, then the right.
+    pub fn goal_areas(&self, dims: (f32, f32)) -> [(Team, Vector2<f32>, Vector2<f32>); 2] {
+        let half_extents = Vector2::new(self.goal_depth / 2.0, self.goal_width / 2.0);
+        [
+            (
+                Team::Green,
+                Vector2::new(half_extents.x, dims.1 / 2.0),
+                half_extents,
+            ),
+            (
+                Team::Red,
+                Vector2::new(dims.0 - half_extents.x, dims.1 / 2.0),
+                half_extents,
+            ),
+        ]
+    }
+}
+
+impl Match {
+    /// A match about to kick off, with `goals` already in the world.
+    pub fn new(settings: MatchSettings, goals: Vec<Goal>, kickoff_seed: u64) -> Self {
+        Match {
+            settings,
+            goals,
+            score: [0, 0],
+            time_left: settings.duration,
+            phase: MatchPhase::Kickoff {
+                remaining: settings.kickoff_delay,
+            },
+            last_scorer: None,
+            kicker: if kickoff_seed & 1 == 0 {
+                Team::Green
+            } else {
+                Team::Red
+            },
+            kickoffs: 0,
+            kickoff_seed,
+            stalled: 0.0,
+        }
+    }
+
+    pub fn score_of(&self, team: Team) -> u32 {
+        self.score[team.index()]
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.phase == MatchPhase::Playing
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.phase == MatchPhase::Finished
+    }
+
+    /// The team ahead, or `None` for a draw.
+    pub fn leader(&self) -> Option<Team> {
+        match self.score_of(Team::Green).cmp(&self.score_of(Team::Red)) {
+            Ordering::Greater => Some(Team::Green),
+            Ordering::Less => Some(Team::Red),
+            Ordering::Equal => None,
+        }
+    }
+
+    /// Random numbers for jittering the next kickoff. Each kickoff gets its
+    /// own sequence, so restoring a snapshot lines up the same way.
+    pub fn kickoff_rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.kickoff_seed.wrapping_add(u64::from(self.kickoffs)))
+    }
+
+    /// The goal `sensor` belongs to, if it is one.
+    pub fn goal_of(&self, sensor: DefaultBodyHandle) -> Option<&Goal> {
+        self.goals.iter().find(|goal| goal.sensor == sensor)
+    }
+
+    /// Counts down the kickoff or the match clock by `dt` seconds.
+    pub fn tick(&mut self, dt: f32) {
+        match self.phase {
+            MatchPhase::Kickoff { remaining } => {
+                self.phase = if remaining <= dt {
+                    MatchPhase::Playing
+                } else {
+                    MatchPhase::Kickoff {
+                        remaining: remaining - dt,
+                    }
+                };
+            }
+            MatchPhase::Playing => {
+                self.time_left = (self.time_left - dt).max(0.0);
+                if self.time_left <= 0.0 {
+                    self.phase = MatchPhase::Finished;
+                }
+            }
+            MatchPhase::Finished => (),
+        }
+    }
+
+    /// Times how long the ball has been moving slower than `speed`, and
+    /// says whether it has been stuck long enough to restart play.
+    pub fn ball_stalled(&mut self, speed: f32, dt: f32) -> bool {
+        if self.is_playing() && speed < STALL_SPEED {
+            self.stalled += dt;
+        } else {
+            self.stalled = 0.0;
+        }
+        self.stalled >= STALL_TIME
+    }
+
+    /// Records a goal for `scorer` and lines up for the next kickoff, which
+    /// the other side takes.
+    pub fn goal_scored(&mut self, scorer: Team) {
+        self.score[scorer.index()] += 1;
+        self.last_scorer = Some(scorer);
+        self.kick_off(scorer.opponent());
+    }
+
+    /// Stops play when the ball is stuck, handing the next kickoff to
+    /// whoever didn't take the last one.
+    pub fn restart(&mut self) {
+        self.last_scorer = None;
+        self.kick_off(self.kicker.opponent());
+    }
+
+    fn kick_off(&mut self, kicker: Team) {
+        self.kicker = kicker;
+        self.kickoffs += 1;
+        self.stalled = 0.0;
+        self.phase = MatchPhase::Kickoff {
+            remaining: self.settings.kickoff_delay,
+        };
+    }
+
+    /// "Green 1 - 0 Red  1:23", plus how things stand if play is stopped.
+    pub fn scoreboard(&self) -> String {
+        let seconds = self.time_left.ceil() as u32;
+        let mut board = format!(
+            "{} {} - {} {}  {}:{:02}",
+            Team::Green.name(),
+            self.score_of(Team::Green),
+            self.score_of(Team::Red),
+            Team::Red.name(),
+            seconds / 60,
+            seconds % 60
+        );
+        match self.phase {
+            MatchPhase::Kickoff { .. } => match self.last_scorer {
+                Some(team) => board.push_str(&format!("  Goal for {}!", team.name())),
+                None => board.push_str("  Kickoff"),
+            },
+            MatchPhase::Playing => (),
+            MatchPhase::Finished => match self.leader() {
+                Some(team) => board.push_str(&format!("  Full time, {} wins", team.name())),
+                None => board.push_str("  Full time, draw"),
+            },
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_finishes_when_the_clock_runs_out() {
+        let settings = MatchSettings {
+            duration: 2.0,
+            kickoff_delay: 0.5,
+            ..MatchSettings::default()
+        };
+        let mut soccer = Match::new(settings, Vec::new(), 0);
+
+        soccer.tick(0.5);
+        assert!(soccer.is_playing());
+        for _ in 0..7 {
+            soccer.tick(0.25);
+        }
+        assert!(soccer.is_playing());
+        soccer.tick(0.25);
+        assert!(soccer.is_finished());
+
+        soccer.tick(0.25);
+        assert!(soccer.is_finished());
+        assert!(soccer.time_left <= 0.0);
+    }
+
+    #[test]
+    fn kickoff_time_does_not_run_the_clock() {
+        let settings = MatchSettings {
+            duration: 2.0,
+            kickoff_delay: 1.0,
+            ..MatchSettings::default()
+        };
+        let mut soccer = Match::new(settings, Vec::new(), 0);
+
+        soccer.tick(0.5);
+        assert!(matches!(soccer.phase, MatchPhase::Kickoff { .. }));
+        assert!(soccer.time_left >= settings.duration);
+    }
+}
@@ -0,0 +1,37 @@
+use nalgebra::Vector2;
+use nphysics2d::object::DefaultBodyHandle;
+
+// Structures & enums
+
+/// Where a ray met a body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub body: DefaultBodyHandle,
+    pub point: Vector2<f32>,
+    /// Unit normal of the surface that was hit, facing back along the ray.
+    pub normal: Vector2<f32>,
+    /// How far along the ray, in world units.
+    pub distance: f32,
+}
+
+/// Which bodies a spatial query can find. The default sees every solid
+/// body, fixed or not, but no sensors.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryFilter {
+    /// Count sensors too, as trigger zones and goals want.
+    pub sensors: bool,
+    /// Leave out immovable bodies: walls, fixed props and sensors.
+    pub skip_fixed: bool,
+    /// Bodies to leave out, such as whoever is looking.
+    pub exclude: Vec<DefaultBodyHandle>,
+}
+
+impl QueryFilter {
+    /// The default filter, leaving out `body`.
+    pub fn excluding(body: DefaultBodyHandle) -> Self {
+        QueryFilter {
+            exclude: vec![body],
+            ..QueryFilter::default()
+        }
+    }
+}
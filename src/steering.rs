@@ -0,0 +1,556 @@
+use crate::arrival::{ArrivalController, ArrivalSettings};
+use crate::herding::{Herd, HerdSettings};
+use crate::physics::PhysicsStruct;
+
+use nalgebra::Vector2;
+use nphysics2d::object::DefaultBodyHandle;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// Structures & enums
+
+/// Decides how a floater pushes itself around. Each floater holds its own, so
+/// controllers can keep state between steps and be swapped at runtime.
+pub trait FloaterController: Send {
+    fn compute_force(&mut self, floater: &FloaterView, world: &WorldView) -> Steering;
+
+    /// Enough to build the controller again, for snapshots.
+    fn kind(&self) -> ControllerKind;
+
+    /// Called when the floater gets a new target.
+    fn reset(&mut self) {}
+
+    /// Whether the target is somewhere for the floater itself to go, and so
+    /// worth planning a path around obstacles to.
+    fn follows_paths(&self) -> bool {
+        true
+    }
+}
+
+pub enum Steering {
+    Force(Vector2<f32>),
+    /// The floater has reached its target, which gets cleared.
+    Arrived,
+    Idle,
+}
+
+/// The floater being steered, as it was at the start of the step.
+pub struct FloaterView {
+    pub handle: DefaultBodyHandle,
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub radius: f32,
+    pub target: Option<Vector2<f32>>,
+}
+
+/// What controllers get to see of the rest of the world.
+pub struct WorldView<'a> {
+    pub physics: &'a PhysicsStruct,
+    pub beach_ball: DefaultBodyHandle,
+    pub ball_radius: f32,
+    pub dt: f32,
+}
+
+/// Every built-in controller with its settings.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ControllerKind {
+    Seek(Seek),
+    Arrive(ArrivalSettings),
+    Flee(Flee),
+    Wander(WanderSettings),
+    Pursue(Pursue),
+    Flock(Flock),
+    Herd(HerdSettings),
+}
+
+/// Heads straight for the target at full speed and clears it on the way past.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Seek {
+    pub max_speed: f32,
+    pub max_force: f32,
+    /// How hard to correct the difference between the wanted and actual
+    /// velocity, in force per unit of speed.
+    pub responsiveness: f32,
+    pub arrival_radius: f32,
+}
+
+/// Runs from the beach ball while it's within `panic_radius`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Flee {
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub responsiveness: f32,
+    pub panic_radius: f32,
+}
+
+/// Chases where the beach ball is going to be rather than where it is.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Pursue {
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub responsiveness: f32,
+    /// Longest the prediction looks ahead, in seconds.
+    pub max_lookahead: f32,
+}
+
+/// Boids: keeps apart from, lines up with and closes in on nearby floaters,
+/// cruising at `max_speed`. A target becomes the flock's migration goal.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Flock {
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub responsiveness: f32,
+    /// Floaters closer than this count as flockmates.
+    pub neighbour_radius: f32,
+    /// Flockmates closer than this get pushed away from.
+    pub separation_radius: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub migration: f32,
+    pub arrival_radius: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WanderSettings {
+    pub max_speed: f32,
+    pub max_force: f32,
+    pub responsiveness: f32,
+    /// How far ahead the wander circle sits.
+    pub distance: f32,
+    pub radius: f32,
+    /// Largest change of heading on the circle per second, in radians.
+    pub jitter: f32,
+    pub seed: u64,
+}
+
+/// Drifts about by steering toward a point that moves randomly around a
+/// circle in front of the floater. Restarting from a snapshot replays the
+/// random sequence from its seed.
+pub struct Wander {
+    settings: WanderSettings,
+    angle: f32,
+    rng: StdRng,
+}
+
+impl ControllerKind {
+    /// Builds the controller. `salt` is mixed into random seeds so floaters
+    /// given the same kind don't move in lockstep.
+    pub fn build(&self, salt: u64) -> Box<dyn FloaterController> {
+        match *self {
+            ControllerKind::Seek(seek) => Box::new(seek),
+            ControllerKind::Arrive(settings) => Box::new(ArrivalController::new(settings)),
+            ControllerKind::Flee(flee) => Box::new(flee),
+            ControllerKind::Wander(settings) => Box::new(Wander::new(settings, salt)),
+            ControllerKind::Pursue(pursue) => Box::new(pursue),
+            ControllerKind::Flock(flock) => Box::new(flock),
+            ControllerKind::Herd(settings) => Box::new(Herd::new(settings)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControllerKind::Seek(_) => "seek",
+            ControllerKind::Arrive(_) => "arrive",
+            ControllerKind::Flee(_) => "flee",
+            ControllerKind::Wander(_) => "wander",
+            ControllerKind::Pursue(_) => "pursue",
+            ControllerKind::Flock(_) => "flock",
+            ControllerKind::Herd(_) => "herd",
+        }
+    }
+}
+
+impl Default for ControllerKind {
+    fn default() -> Self {
+        ControllerKind::Arrive(ArrivalSettings::default())
+    }
+}
+
+/// The force that turns `velocity` into `desired`, at most `max_force`.
+fn steer_toward(
+    desired: Vector2<f32>,
+    velocity: Vector2<f32>,
+    responsiveness: f32,
+    max_force: f32,
+) -> Vector2<f32> {
+    let force = (desired - velocity) * responsiveness;
+    if force.norm() > max_force {
+        force.normalize() * max_force
+    } else {
+        force
+    }
+}
+
+fn toward(from: Vector2<f32>, to: Vector2<f32>, speed: f32) -> Vector2<f32> {
+    let offset = to - from;
+    if offset.norm() > 0.0 {
+        offset.normalize() * speed
+    } else {
+        Vector2::zeros()
+    }
+}
+
+impl Default for Seek {
+    fn default() -> Self {
+        Seek {
+            max_speed: 200.0,
+            max_force: 1000.0,
+            responsiveness: 5.0,
+            arrival_radius: 10.0,
+        }
+    }
+}
+
+impl FloaterController for Seek {
+    fn compute_force(&mut self, floater: &FloaterView, _world: &WorldView) -> Steering {
+        let target = match floater.target {
+            Some(target) => target,
+            None => return Steering::Idle,
+        };
+        if (target - floater.position).norm() <= self.arrival_radius {
+            return Steering::Arrived;
+        }
+
+        let desired = toward(floater.position, target, self.max_speed);
+        Steering::Force(steer_toward(
+            desired,
+            floater.velocity,
+            self.responsiveness,
+            self.max_force,
+        ))
+    }
+
+    fn kind(&self) -> ControllerKind {
+        ControllerKind::Seek(*self)
+    }
+}
+
+impl FloaterController for ArrivalController {
+    fn compute_force(&mut self, floater: &FloaterView, world: &WorldView) -> Steering {
+        match floater.target {
+            Some(target) => self.steer(floater.position, floater.velocity, target, world.dt),
+            None => Steering::Idle,
+        }
+    }
+
+    fn kind(&self) -> ControllerKind {
+        ControllerKind::Arrive(self.settings())
+    }
+
+    fn reset(&mut self) {
+        ArrivalController::reset(self);
+    }
+}
+
+impl Default for Flee {
+    fn default() -> Self {
+        Flee {
+            max_speed: 200.0,
+            max_force: 1000.0,
+            responsiveness: 5.0,
+            panic_radius: 150.0,
+        }
+    }
+}
+
+impl FloaterController for Flee {
+    fn compute_force(&mut self, floater: &FloaterView, world: &WorldView) -> Steering {
+        let threat = match world.physics.try_get_pos_of(world.beach_ball) {
+            Ok(threat) => threat,
+            Err(_) => return Steering::Idle,
+        };
+        if (threat - floater.position).norm() > self.panic_radius {
+            return Steering::Idle;
+        }
+
+        let desired = toward(threat, floater.position, self.max_speed);
+        Steering::Force(steer_toward(
+            desired,
+            floater.velocity,
+            self.responsiveness,
+            self.max_force,
+        ))
+    }
+
+    fn kind(&self) -> ControllerKind {
+        ControllerKind::Flee(*self)
+    }
+}
+
+impl Default for Pursue {
+    fn default() -> Self {
+        Pursue {
+            max_speed: 200.0,
+            max_force: 1000.0,
+            responsiveness: 5.0,
+            max_lookahead: 1.0,
+        }
+    }
+}
+
+impl FloaterController for Pursue {
+    fn compute_force(&mut self, floater: &FloaterView, world: &WorldView) -> Steering {
+        let (quarry, quarry_velocity) = match (
+            world.physics.try_get_pos_of(world.beach_ball),
+            world.physics.try_get_vel_of(world.beach_ball),
+        ) {
+            (Ok(position), Ok(velocity)) => (position, velocity),
+            _ => return Steering::Idle,
+        };
+
+        // Look further ahead the longer it would take to get there.
+        let lookahead =
+            ((quarry - floater.position).norm() / self.max_speed).min(self.max_lookahead);
+        let predicted = quarry + quarry_velocity * lookahead;
+
+        let desired = toward(floater.position, predicted, self.max_speed);
+        Steering::Force(steer_toward(
+            desired,
+            floater.velocity,
+            self.responsiveness,
+            self.max_force,
+        ))
+    }
+
+    fn kind(&self) -> ControllerKind {
+        ControllerKind::Pursue(*self)
+    }
+}
+
+impl Default for Flock {
+    fn default() -> Self {
+        Flock {
+            max_speed: 120.0,
+            max_force: 600.0,
+            responsiveness: 3.0,
+            neighbour_radius: 80.0,
+            separation_radius: 30.0,
+            separation: 1.5,
+            alignment: 1.0,
+            cohesion: 1.0,
+            migration: 1.0,
+            arrival_radius: 30.0,
+        }
+    }
+}
+
+impl FloaterController for Flock {
+    fn compute_force(&mut self, floater: &FloaterView, world: &WorldView) -> Steering {
+        if let Some(target) = floater.target {
+            if (target - floater.position).norm() <= self.arrival_radius {
+                return Steering::Arrived;
+            }
+        }
+
+        let mates: Vec<(Vector2<f32>, Vector2<f32>)> = world
+            .physics
+            .bodies_within(floater.position, self.neighbour_radius)
+            .into_iter()
+            .filter(|&handle| handle != floater.handle && handle != world.beach_ball)
+            .filter_map(|handle| {
+                Some((
+                    world.physics.try_get_pos_of(handle).ok()?,
+                    world.physics.try_get_vel_of(handle).ok()?,
+                ))
+            })
+            .collect();
+
+        let mut desired = Vector2::zeros();
+        if !mates.is_empty() {
+            let count = mates.len() as f32;
+            let mut away = Vector2::zeros();
+            for &(position, _) in mates.iter() {
+                let offset = floater.position - position;
+                let distance = offset.norm();
+                if distance > 0.0 && distance < self.separation_radius {
+                    // Closer mates push harder.
+                    away += offset / (distance * distance);
+                }
+            }
+            let heading = mates
+                .iter()
+                .fold(Vector2::zeros(), |sum, &(_, velocity)| sum + velocity)
+                / count;
+            let center = mates
+                .iter()
+                .fold(Vector2::zeros(), |sum, &(position, _)| sum + position)
+                / count;
+
+            desired += toward(Vector2::zeros(), away, self.max_speed) * self.separation;
+            desired += toward(Vector2::zeros(), heading, self.max_speed) * self.alignment;
+            desired += toward(floater.position, center, self.max_speed) * self.cohesion;
+        }
+        if let Some(target) = floater.target {
+            desired += toward(floater.position, target, self.max_speed) * self.migration;
+        }
+
+        // Keep cruising the way we're going when nothing pulls anywhere.
+        let desired = if desired.norm() > 0.0 {
+            desired.normalize() * self.max_speed
+        } else {
+            toward(Vector2::zeros(), floater.velocity, self.max_speed)
+        };
+        if desired.norm() == 0.0 {
+            return Steering::Idle;
+        }
+        Steering::Force(steer_toward(
+            desired,
+            floater.velocity,
+            self.responsiveness,
+            self.max_force,
+        ))
+    }
+
+    fn kind(&self) -> ControllerKind {
+        ControllerKind::Flock(*self)
+    }
+}
+
+impl Default for WanderSettings {
+    fn default() -> Self {
+        WanderSettings {
+            max_speed: 80.0,
+            max_force: 500.0,
+            responsiveness: 3.0,
+            distance: 40.0,
+            radius: 20.0,
+            jitter: 4.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Wander {
+    pub fn new(settings: WanderSettings, salt: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(settings.seed ^ salt);
+        let angle = rng.gen_range(0.0, std::f32::consts::PI * 2.0);
+
+        Wander {
+            settings,
+            angle,
+            rng,
+        }
+    }
+}
+
+impl FloaterController for Wander {
+    fn compute_force(&mut self, floater: &FloaterView, world: &WorldView) -> Steering {
+        let settings = self.settings;
+        let max_turn = settings.jitter * world.dt;
+        if max_turn > 0.0 {
+            self.angle += self.rng.gen_range(-max_turn, max_turn);
+        }
+
+        let heading = if floater.velocity.norm() > 0.0 {
+            floater.velocity.normalize()
+        } else {
+            Vector2::new(self.angle.cos(), self.angle.sin())
+        };
+        let wander_point = floater.position
+            + heading * settings.distance
+            + Vector2::new(self.angle.cos(), self.angle.sin()) * settings.radius;
+
+        let desired = toward(floater.position, wander_point, settings.max_speed);
+        Steering::Force(steer_toward(
+            desired,
+            floater.velocity,
+            settings.responsiveness,
+            settings.max_force,
+        ))
+    }
+
+    fn kind(&self) -> ControllerKind {
+        ControllerKind::Wander(self.settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A world with a far-off beach ball and a floater at each of `at`.
+    fn world(at: &[Vector2<f32>]) -> (PhysicsStruct, DefaultBodyHandle, Vec<DefaultBodyHandle>) {
+        let mut physics = PhysicsStruct::new((400.0, 400.0));
+        let ball = physics.create_ball(20.0, Vector2::new(350.0, 350.0), 0.001);
+        let floaters = at
+            .iter()
+            .map(|&position| physics.create_ball(5.0, position, 0.003))
+            .collect();
+        physics.sync_geometry();
+        (physics, ball, floaters)
+    }
+
+    fn flock(
+        physics: &PhysicsStruct,
+        ball: DefaultBodyHandle,
+        floater: DefaultBodyHandle,
+        target: Option<Vector2<f32>>,
+    ) -> Steering {
+        let view = WorldView {
+            physics,
+            beach_ball: ball,
+            ball_radius: 20.0,
+            dt: physics.timestep(),
+        };
+        let state = FloaterView {
+            handle: floater,
+            position: physics.get_pos_of(floater),
+            velocity: physics.get_vel_of(floater),
+            radius: 5.0,
+            target,
+        };
+        Flock::default().compute_force(&state, &view)
+    }
+
+    #[test]
+    fn flockmates_too_close_push_apart() {
+        let (physics, ball, floaters) =
+            world(&[Vector2::new(100.0, 100.0), Vector2::new(115.0, 100.0)]);
+        match flock(&physics, ball, floaters[0], None) {
+            Steering::Force(force) => assert!(force.x < 0.0, "force {:?}", force),
+            _ => panic!("expected a push away"),
+        }
+    }
+
+    #[test]
+    fn distant_flockmates_draw_together() {
+        let (physics, ball, floaters) =
+            world(&[Vector2::new(100.0, 100.0), Vector2::new(160.0, 100.0)]);
+        match flock(&physics, ball, floaters[0], None) {
+            Steering::Force(force) => assert!(force.x > 0.0, "force {:?}", force),
+            _ => panic!("expected a pull together"),
+        }
+    }
+
+    #[test]
+    fn a_lone_floater_migrates_to_its_target_and_arrives() {
+        let (physics, ball, floaters) = world(&[Vector2::new(100.0, 100.0)]);
+        match flock(
+            &physics,
+            ball,
+            floaters[0],
+            Some(Vector2::new(100.0, 300.0)),
+        ) {
+            Steering::Force(force) => assert!(force.y > 0.0 && force.x.abs() < 1e-3),
+            _ => panic!("expected a pull to the target"),
+        }
+        let arrived = flock(
+            &physics,
+            ball,
+            floaters[0],
+            Some(Vector2::new(110.0, 100.0)),
+        );
+        assert!(matches!(arrived, Steering::Arrived));
+        assert!(matches!(
+            flock(&physics, ball, floaters[0], None),
+            Steering::Idle
+        ));
+    }
+}
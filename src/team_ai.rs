@@ -0,0 +1,496 @@
+use crate::arrival::ArrivalSettings;
+use crate::floater::{Orders, PhysicsBall};
+use crate::herding::HerdSettings;
+use crate::soccer::{Goal, Team};
+use crate::steering::{ControllerKind, Pursue};
+use crate::world::TopDownWorld;
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+// Constants
+/// How far the keeper stands out from its goal line.
+const KEEPER_OFFSET: f32 = 15.0;
+/// Where defenders hold, as a fraction of the way from their goal to the ball.
+const DEFENDER_DEPTH: f32 = 0.35;
+/// Closest defenders come to their goal, along x, leaving the mouth to the
+/// keeper.
+const DEFENDER_MIN_DEPTH: f32 = 120.0;
+/// Vertical gap between defenders.
+const DEFENDER_SPACING: f32 = 50.0;
+
+// Structures & enums
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// How a computer team splits up the work.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Tactics {
+    /// A keeper, one or two attackers and the rest defending.
+    Roles,
+    /// Everyone herds the ball toward the other goal.
+    Swarm,
+    /// Everyone runs at the ball.
+    Chase,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// Herds the ball toward the other goal.
+    Attacker,
+    /// Sits between the ball and its own goal.
+    Defender,
+    /// Guards the goal mouth.
+    Keeper,
+    /// Runs at the ball with no plan beyond that.
+    Chaser,
+}
+
+/// A computer-controlled soccer team. It only hands out orders, by setting
+/// controllers and targets on its floaters, so its players are pushed by the
+/// same forces a human's would be.
+pub struct TeamAi {
+    team: Team,
+    tactics: Tactics,
+    difficulty: Difficulty,
+    /// Seconds until the next decision.
+    cooldown: f32,
+    keeper: Option<usize>,
+}
+
+impl Difficulty {
+    /// Seconds between decisions.
+    pub fn reaction_time(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.8,
+            Difficulty::Normal => 0.4,
+            Difficulty::Hard => 0.15,
+        }
+    }
+
+    /// The strongest push its players are allowed.
+    pub fn max_force(self) -> f32 {
+        match self {
+            Difficulty::Easy => 500.0,
+            Difficulty::Normal => 800.0,
+            Difficulty::Hard => 1000.0,
+        }
+    }
+
+    /// How fast its attackers drive the ball. Pushing against the other
+    /// team is a contest of this as much as of force.
+    pub fn push_speed(self) -> f32 {
+        match self {
+            Difficulty::Easy => 60.0,
+            Difficulty::Normal => 100.0,
+            Difficulty::Hard => 150.0,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+impl Tactics {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tactics::Roles => "roles",
+            Tactics::Swarm => "swarm",
+            Tactics::Chase => "chase",
+        }
+    }
+}
+
+impl TeamAi {
+    pub fn new(team: Team, tactics: Tactics, difficulty: Difficulty) -> Self {
+        TeamAi {
+            team,
+            tactics,
+            difficulty,
+            cooldown: 0.0,
+            keeper: None,
+        }
+    }
+
+    pub fn team(&self) -> Team {
+        self.team
+    }
+
+    pub fn tactics(&self) -> Tactics {
+        self.tactics
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Forgets the current roles, so the team sorts itself out afresh after
+    /// a kickoff.
+    pub fn reset(&mut self) {
+        self.cooldown = 0.0;
+        self.keeper = None;
+    }
+
+    /// Gives out orders if it's time to decide again.
+    pub fn update(&mut self, world: &mut TopDownWorld, dt: f32) {
+        self.cooldown -= dt;
+        if self.cooldown > 0.0 {
+            return;
+        }
+        self.cooldown = self.difficulty.reaction_time();
+
+        let goals = match &world.soccer {
+            Some(soccer) => soccer.goals.clone(),
+            None => return,
+        };
+        let own_goal = match goals.iter().find(|goal| goal.defender == self.team) {
+            Some(goal) => *goal,
+            None => return,
+        };
+        let their_goal = match goals.iter().find(|goal| goal.defender != self.team) {
+            Some(goal) => *goal,
+            None => return,
+        };
+
+        // Aim for the side of the mouth away from whoever is closest to
+        // stopping us, rather than pushing straight into them.
+        let ball = match world.physics.try_get_pos_of(world.beach_ball.get_handle()) {
+            Ok(ball) => ball,
+            Err(_) => return,
+        };
+        let blocker = world
+            .team_members(self.team.opponent())
+            .into_iter()
+            .filter_map(|index| {
+                world
+                    .physics
+                    .try_get_pos_of(world.floaters[index].get_handle())
+                    .ok()
+            })
+            .min_by(|a, b| {
+                (a - ball)
+                    .norm()
+                    .partial_cmp(&(b - ball).norm())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+        let reach = (their_goal.half_extents.y - world.beach_ball.get_radius()).max(0.0);
+        let shot = match blocker {
+            Some(blocker) if blocker.y > ball.y => their_goal.center - Vector2::new(0.0, reach),
+            Some(_) => their_goal.center + Vector2::new(0.0, reach),
+            None => their_goal.center,
+        };
+
+        let plan = self.plan(world, &own_goal);
+        let attackers = plan
+            .iter()
+            .filter(|(_, role, _)| *role == Role::Attacker)
+            .count();
+        let radius = world
+            .floaters
+            .iter()
+            .map(|floater| floater.get_radius())
+            .fold(0.0, f32::max);
+        let mut slots =
+            HerdSettings::slots(attackers, radius, world.beach_ball.get_radius()).into_iter();
+
+        for (index, role, target) in plan {
+            let controller = match role {
+                Role::Attacker => ControllerKind::Herd(HerdSettings {
+                    max_force: self.difficulty.max_force(),
+                    push_speed: self.difficulty.push_speed(),
+                    slot: slots.next().unwrap_or(0.0),
+                    ..HerdSettings::default()
+                }),
+                Role::Defender | Role::Keeper => ControllerKind::Arrive(ArrivalSettings {
+                    max_force: self.difficulty.max_force(),
+                    ..ArrivalSettings::default()
+                }),
+                Role::Chaser => ControllerKind::Pursue(Pursue {
+                    max_force: self.difficulty.max_force(),
+                    ..Pursue::default()
+                }),
+            };
+            let target = match role {
+                Role::Attacker => Some(shot),
+                _ => target,
+            };
+
+            let floater = &mut world.floaters[index];
+            let changed = floater.controller.kind() != controller;
+            if changed {
+                floater.set_controller(controller.build(index as u64));
+            }
+            match target {
+                Some(target) if changed => floater.set_target(target),
+                // Same job, so keep the controller's state.
+                Some(target) => floater.target = Some(target),
+                None => {
+                    floater.target = None;
+                    floater.orders = Orders::default();
+                }
+            }
+        }
+    }
+
+    /// A role for every player, with where to go. Attackers and chasers have
+    /// no target of their own: they go by the ball.
+    fn plan(
+        &mut self,
+        world: &TopDownWorld,
+        own_goal: &Goal,
+    ) -> Vec<(usize, Role, Option<Vector2<f32>>)> {
+        // Floaters whose bodies are gone get no role, so every lookup of a
+        // member's position below succeeds.
+        let members: Vec<usize> = world
+            .team_members(self.team)
+            .into_iter()
+            .filter(|&index| {
+                world
+                    .physics
+                    .try_get_pos_of(world.floaters[index].get_handle())
+                    .is_ok()
+            })
+            .collect();
+        let ball = match world.physics.try_get_pos_of(world.beach_ball.get_handle()) {
+            Ok(ball) if !members.is_empty() => ball,
+            _ => return Vec::new(),
+        };
+        let position_of = |index: usize| {
+            world
+                .physics
+                .try_get_pos_of(world.floaters[index].get_handle())
+                .expect("Members without bodies were filtered out above")
+        };
+        let closest = |from: Vector2<f32>, among: &[usize]| {
+            among.iter().copied().min_by(|&a, &b| {
+                let a = (position_of(a) - from).norm();
+                let b = (position_of(b) - from).norm();
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+        };
+
+        let mut plan = Vec::new();
+        match self.tactics {
+            Tactics::Roles => (),
+            Tactics::Swarm => {
+                for index in members {
+                    plan.push((index, Role::Attacker, None));
+                }
+                return plan;
+            }
+            Tactics::Chase => {
+                for index in members {
+                    plan.push((index, Role::Chaser, None));
+                }
+                return plan;
+            }
+        }
+        let mut field: Vec<usize> = members.clone();
+
+        // Keep the same keeper until the next kickoff.
+        if members.len() > 2 {
+            let keeper = match self.keeper.filter(|keeper| members.contains(keeper)) {
+                Some(keeper) => keeper,
+                None => closest(own_goal.center, &field).expect("Team is not empty"),
+            };
+            self.keeper = Some(keeper);
+            field.retain(|&index| index != keeper);
+
+            let radius = world.floaters[keeper].get_radius();
+            let reach = (own_goal.half_extents.y - radius).max(0.0);
+            let x = own_goal.center.x
+                + self.team.attack_direction() * (own_goal.half_extents.x + radius + KEEPER_OFFSET);
+            let y = ball
+                .y
+                .max(own_goal.center.y - reach)
+                .min(own_goal.center.y + reach);
+            plan.push((keeper, Role::Keeper, Some(Vector2::new(x, y))));
+        }
+
+        let attackers = if field.len() > 3 { 2 } else { 1 };
+        for _ in 0..attackers.min(field.len()) {
+            let attacker = closest(ball, &field).expect("Field is not empty");
+            field.retain(|&index| index != attacker);
+            plan.push((attacker, Role::Attacker, None));
+        }
+
+        // The rest line up across the way from the ball to the goal.
+        let mut guard = own_goal.center + (ball - own_goal.center) * DEFENDER_DEPTH;
+        let depth = (guard.x - own_goal.center.x) * self.team.attack_direction();
+        guard.x += self.team.attack_direction() * (DEFENDER_MIN_DEPTH - depth).max(0.0);
+        field.sort_by(|&a, &b| {
+            position_of(a)
+                .y
+                .partial_cmp(&position_of(b).y)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let half = (field.len().max(1) - 1) as f32 / 2.0;
+        for (place, &defender) in field.iter().enumerate() {
+            let offset = Vector2::new(0.0, (place as f32 - half) * DEFENDER_SPACING);
+            plan.push((defender, Role::Defender, Some(guard + offset)));
+        }
+        plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::scene::Scene;
+
+    use nalgebra::Isometry2;
+    use nphysics2d::math::Velocity;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// A soccer world with `per_team` floaters a side, Green's lined up
+    /// at `green`, and the ball at the centre spot.
+    fn soccer_world(per_team: u32, green: &[Vector2<f32>]) -> (TopDownWorld, Goal) {
+        let mut scene = Scene::soccer();
+        scene.bodies[0].count = per_team * 2;
+        let mut world = TopDownWorld::from_scene(&scene, &mut StdRng::seed_from_u64(0))
+            .expect("Soccer scene is invalid");
+        for (&index, &position) in world.team_members(Team::Green).iter().zip(green) {
+            let handle = world.floaters[index].get_handle();
+            world
+                .physics
+                .set_state_of(handle, Isometry2::new(position, 0.0), Velocity::zero());
+        }
+        let ball = world.beach_ball.get_handle();
+        world.physics.set_state_of(
+            ball,
+            Isometry2::new(Vector2::new(400.0, 300.0), 0.0),
+            Velocity::zero(),
+        );
+        let own_goal = *world
+            .soccer
+            .as_ref()
+            .unwrap()
+            .goals
+            .iter()
+            .find(|goal| goal.defender == Team::Green)
+            .unwrap();
+        (world, own_goal)
+    }
+
+    fn with_role(plan: &[(usize, Role, Option<Vector2<f32>>)], role: Role) -> Vec<usize> {
+        plan.iter()
+            .filter(|(_, r, _)| *r == role)
+            .map(|&(index, _, _)| index)
+            .collect()
+    }
+
+    fn ai() -> TeamAi {
+        TeamAi::new(Team::Green, Tactics::Roles, Difficulty::Normal)
+    }
+
+    #[test]
+    fn four_players_split_into_keeper_attacker_and_two_defenders() {
+        let spots = [
+            Vector2::new(60.0, 300.0),
+            Vector2::new(380.0, 300.0),
+            Vector2::new(200.0, 150.0),
+            Vector2::new(200.0, 450.0),
+        ];
+        let (world, own_goal) = soccer_world(4, &spots);
+        let members = world.team_members(Team::Green);
+        let plan = ai().plan(&world, &own_goal);
+
+        assert_eq!(plan.len(), 4);
+        assert_eq!(with_role(&plan, Role::Keeper), vec![members[0]]);
+        assert_eq!(with_role(&plan, Role::Attacker), vec![members[1]]);
+        let mut defenders = with_role(&plan, Role::Defender);
+        defenders.sort_unstable();
+        assert_eq!(defenders, vec![members[2], members[3]]);
+    }
+
+    #[test]
+    fn squads_of_five_or_more_send_two_attackers() {
+        let spots = [
+            Vector2::new(60.0, 300.0),
+            Vector2::new(380.0, 300.0),
+            Vector2::new(370.0, 250.0),
+            Vector2::new(200.0, 150.0),
+            Vector2::new(200.0, 450.0),
+        ];
+        let (world, own_goal) = soccer_world(5, &spots);
+        let members = world.team_members(Team::Green);
+        let plan = ai().plan(&world, &own_goal);
+
+        let mut attackers = with_role(&plan, Role::Attacker);
+        attackers.sort_unstable();
+        assert_eq!(attackers, vec![members[1], members[2]]);
+        assert_eq!(with_role(&plan, Role::Defender).len(), 2);
+
+        let (world, own_goal) = soccer_world(3, &spots);
+        let plan = ai().plan(&world, &own_goal);
+        assert_eq!(with_role(&plan, Role::Attacker).len(), 1);
+        assert_eq!(with_role(&plan, Role::Defender).len(), 1);
+    }
+
+    #[test]
+    fn keeper_is_kept_until_reset() {
+        let spots = [
+            Vector2::new(60.0, 300.0),
+            Vector2::new(380.0, 300.0),
+            Vector2::new(200.0, 150.0),
+            Vector2::new(200.0, 450.0),
+        ];
+        let (mut world, own_goal) = soccer_world(4, &spots);
+        let members = world.team_members(Team::Green);
+        let mut ai = ai();
+        ai.plan(&world, &own_goal);
+
+        // Swap the keeper and a defender round.
+        for &(index, position) in [(members[0], spots[2]), (members[2], spots[0])].iter() {
+            let handle = world.floaters[index].get_handle();
+            world
+                .physics
+                .set_state_of(handle, Isometry2::new(position, 0.0), Velocity::zero());
+        }
+        let plan = ai.plan(&world, &own_goal);
+        assert_eq!(with_role(&plan, Role::Keeper), vec![members[0]]);
+
+        ai.reset();
+        let plan = ai.plan(&world, &own_goal);
+        assert_eq!(with_role(&plan, Role::Keeper), vec![members[2]]);
+    }
+
+    #[test]
+    fn defenders_line_up_evenly_spaced() {
+        let spots = [
+            Vector2::new(60.0, 300.0),
+            Vector2::new(380.0, 300.0),
+            Vector2::new(200.0, 100.0),
+            Vector2::new(200.0, 300.0),
+            Vector2::new(200.0, 500.0),
+            Vector2::new(370.0, 250.0),
+        ];
+        let (world, own_goal) = soccer_world(6, &spots);
+        let plan = ai().plan(&world, &own_goal);
+
+        let mut targets: Vec<Vector2<f32>> = plan
+            .iter()
+            .filter(|(_, role, _)| *role == Role::Defender)
+            .map(|(_, _, target)| target.expect("Defenders have somewhere to stand"))
+            .collect();
+        assert_eq!(targets.len(), 3);
+        targets.sort_by(|a, b| a.y.partial_cmp(&b.y).unwrap());
+        for pair in targets.windows(2) {
+            assert!((pair[1].y - pair[0].y - DEFENDER_SPACING).abs() < 1e-3);
+            assert!((pair[1].x - pair[0].x).abs() < 1e-3);
+        }
+        assert!((targets[0].x - own_goal.center.x).abs() >= DEFENDER_MIN_DEPTH - 1e-3);
+    }
+}
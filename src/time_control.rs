@@ -0,0 +1,149 @@
+use ggez::event::KeyCode;
+
+use std::time::Duration;
+
+// Constants
+pub const SPEEDS: [f32; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+const NORMAL_SPEED: usize = 2;
+
+// Structures & enums
+
+/// Pause, single-step and speed settings. Speed changes how much simulated
+/// time each frame is worth rather than the physics timestep, so runs stay
+/// deterministic at any speed.
+pub struct TimeControl {
+    paused: bool,
+    speed: usize,
+    single_steps: u32,
+}
+
+impl TimeControl {
+    pub fn new() -> Self {
+        TimeControl {
+            paused: false,
+            speed: NORMAL_SPEED,
+            single_steps: 0,
+        }
+    }
+
+    /// P pauses, `.` steps once while paused, `[` and `]` change speed and
+    /// `\` goes back to normal speed. Returns whether the key was used.
+    pub fn handle_key(&mut self, keycode: KeyCode) -> bool {
+        match keycode {
+            KeyCode::P => self.toggle_pause(),
+            KeyCode::Period => self.step_once(),
+            KeyCode::LBracket => self.slower(),
+            KeyCode::RBracket => self.faster(),
+            KeyCode::Backslash => self.speed = NORMAL_SPEED,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Pauses, then queues one step.
+    pub fn step_once(&mut self) {
+        self.paused = true;
+        self.single_steps += 1;
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> f32 {
+        SPEEDS[self.speed]
+    }
+
+    /// How much simulated time a frame of `frame_time` is worth.
+    pub fn scale(&self, frame_time: Duration) -> Duration {
+        if self.paused {
+            Duration::from_secs(0)
+        } else {
+            frame_time.mul_f32(self.speed())
+        }
+    }
+
+    pub fn take_single_steps(&mut self) -> u32 {
+        let steps = self.single_steps;
+        self.single_steps = 0;
+        steps
+    }
+
+    pub fn label(&self, tick: u64) -> String {
+        if self.paused {
+            format!("paused | tick {}", tick)
+        } else {
+            format!("{}x | tick {}", self.speed(), tick)
+        }
+    }
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pausing_stops_time() {
+        let mut time = TimeControl::new();
+        let frame = Duration::from_millis(500);
+        assert_eq!(time.scale(frame), frame);
+        time.toggle_pause();
+        assert!(time.is_paused());
+        assert_eq!(time.scale(frame), Duration::from_secs(0));
+        time.toggle_pause();
+        assert_eq!(time.scale(frame), frame);
+    }
+
+    #[test]
+    fn single_steps_pause_and_are_taken_once() {
+        let mut time = TimeControl::new();
+        time.step_once();
+        time.step_once();
+        assert!(time.is_paused());
+        assert_eq!(time.take_single_steps(), 2);
+        assert_eq!(time.take_single_steps(), 0);
+    }
+
+    #[test]
+    fn speed_stays_within_the_settings() {
+        let mut time = TimeControl::new();
+        for _ in 0..SPEEDS.len() + 2 {
+            time.faster();
+        }
+        assert_eq!(time.speed, SPEEDS.len() - 1);
+        for _ in 0..SPEEDS.len() + 2 {
+            time.slower();
+        }
+        assert_eq!(time.speed, 0);
+        assert!(time.handle_key(KeyCode::Backslash));
+        assert_eq!(time.speed, NORMAL_SPEED);
+    }
+
+    #[test]
+    fn speed_scales_frame_time() {
+        let mut time = TimeControl::new();
+        time.faster();
+        assert_eq!(
+            time.scale(Duration::from_millis(250)),
+            Duration::from_millis(500)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::physics::{PhysicsError, PhysicsStruct};
+
+use nalgebra::Vector2;
+use nphysics2d::object::DefaultBodyHandle;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Constants
+pub const DEFAULT_RATE: u32 = 60;
+pub const DEFAULT_MAX_SUBSTEPS: u32 = 5;
+
+// Structures & enums
+
+/// Turns variable frame times into a whole number of fixed physics steps.
+pub struct FixedTimestep {
+    rate: u32,
+    max_substeps: u32,
+    accumulator: f32,
+}
+
+/// Positions from just before the latest step, so frames drawn between steps
+/// can blend between the two states.
+#[derive(Default)]
+pub struct Interpolation {
+    previous: HashMap<DefaultBodyHandle, Vector2<f32>>,
+}
+
+impl FixedTimestep {
+    pub fn new(rate: u32, max_substeps: u32) -> Self {
+        FixedTimestep {
+            rate: rate.max(1),
+            max_substeps: max_substeps.max(1),
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    pub fn dt(&self) -> f32 {
+        1.0 / self.rate as f32
+    }
+
+    /// Adds a frame's worth of time and returns how many steps to run. Time
+    /// beyond `max_substeps` steps is dropped so a slow frame can't snowball.
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        let dt = self.dt();
+        self.accumulator += frame_time.as_secs_f32();
+
+        let mut steps = 0;
+        while self.accumulator >= dt && steps < self.max_substeps {
+            self.accumulator -= dt;
+            steps += 1;
+        }
+        if steps == self.max_substeps {
+            self.accumulator = self.accumulator.min(dt);
+        }
+
+        steps
+    }
+
+    /// How far the next step has progressed, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.dt()).min(1.0)
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        FixedTimestep::new(DEFAULT_RATE, DEFAULT_MAX_SUBSTEPS)
+    }
+}
+
+impl Interpolation {
+    /// Call right before stepping `physics`.
+    pub fn capture(&mut self, physics: &PhysicsStruct) {
+        self.previous.clear();
+        for (handle, _) in physics.bodies.iter() {
+            if let Some(body) = physics.bodies.rigid_body(handle) {
+                self.previous
+                    .insert(handle, body.position().translation.vector);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.previous.clear();
+    }
+
+    /// Where to draw `handle`, `alpha` of the way from its previous position
+    /// to its current one.
+    pub fn pos_of(
+        &self,
+        physics: &PhysicsStruct,
+        handle: DefaultBodyHandle,
+        alpha: f32,
+    ) -> Vector2<f32> {
+        self.try_pos_of(physics, handle, alpha)
+            .expect("Object not found")
+    }
+
+    /// Like `pos_of`, for bodies that may have been removed.
+    pub fn try_pos_of(
+        &self,
+        physics: &PhysicsStruct,
+        handle: DefaultBodyHandle,
+        alpha: f32,
+    ) -> Result<Vector2<f32>, PhysicsError> {
+        let current = physics.try_get_pos_of(handle)?;
+        Ok(match self.previous.get(&handle) {
+            Some(previous) => previous.lerp(&current, alpha),
+            None => current,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_steps_are_taken_and_the_rest_carried_over() {
+        let mut timestep = FixedTimestep::new(10, 5);
+        assert_eq!(timestep.advance(Duration::from_millis(60)), 0);
+        assert!(timestep.alpha() > 0.5 && timestep.alpha() < 0.7);
+        assert_eq!(timestep.advance(Duration::from_millis(60)), 1);
+        assert_eq!(timestep.advance(Duration::from_millis(250)), 2);
+    }
+
+    #[test]
+    fn substeps_are_capped_and_the_backlog_dropped() {
+        let mut timestep = FixedTimestep::new(10, 3);
+        assert_eq!(timestep.advance(Duration::from_secs(10)), 3);
+        // At most one step's worth is kept for the next frame.
+        assert_eq!(timestep.advance(Duration::from_secs(0)), 1);
+        assert_eq!(timestep.advance(Duration::from_secs(0)), 0);
+    }
+
+    #[test]
+    fn rate_and_substeps_are_at_least_one() {
+        let mut timestep = FixedTimestep::new(0, 0);
+        assert_eq!(timestep.rate(), 1);
+        assert_eq!(timestep.advance(Duration::from_secs(5)), 1);
+    }
+}
@@ -0,0 +1,449 @@
+use crate::floater::PhysicsBall;
+use crate::scene::{Scene, SceneError};
+use crate::soccer::Team;
+use crate::team_ai::{Difficulty, Tactics, TeamAi};
+use crate::world::TopDownWorld;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use std::cmp::Ordering;
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// Constants
+/// How far past touching the ball a floater can be and still have it.
+const POSSESSION_REACH: f32 = 10.0;
+const WIN_POINTS: u32 = 3;
+const DRAW_POINTS: u32 = 1;
+
+// Structures & enums
+
+/// A way of playing a team that can be entered into a tournament.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Strategy {
+    pub name: String,
+    pub tactics: Tactics,
+    pub difficulty: Difficulty,
+}
+
+/// One match to play: strategies by index into the entrants, and the seed
+/// the world is built with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fixture {
+    pub green: usize,
+    pub red: usize,
+    pub seed: u64,
+}
+
+/// What happened in a fixture. Per-team figures are Green then Red.
+#[derive(Clone, Debug)]
+pub struct MatchResult {
+    pub fixture: Fixture,
+    pub score: [u32; 2],
+    /// Seconds of play each team was nearest the ball and touching it.
+    pub possession: [f32; 2],
+    /// Mean speed of each team's floaters over the time in play.
+    pub average_speed: [f32; 2],
+}
+
+/// A strategy's line in the leaderboard.
+#[derive(Clone, Debug, Default)]
+pub struct Standing {
+    pub strategy: String,
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub goals_for: u32,
+    pub goals_against: u32,
+    /// Share of possessed time, from 0 to 1.
+    pub possession: f32,
+    pub average_speed: f32,
+    pub points: u32,
+}
+
+impl Strategy {
+    /// Every strategy the tournament knows about: each set of tactics at
+    /// each difficulty, named like `roles-hard`.
+    pub fn registered() -> Vec<Strategy> {
+        let mut strategies = Vec::new();
+        for &tactics in [Tactics::Roles, Tactics::Swarm, Tactics::Chase].iter() {
+            for &difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard].iter() {
+                strategies.push(Strategy {
+                    name: format!("{}-{}", tactics.name(), difficulty.name()),
+                    tactics,
+                    difficulty,
+                });
+            }
+        }
+        strategies
+    }
+
+    /// The registered strategy called `name`.
+    pub fn find(name: &str) -> Option<Strategy> {
+        Strategy::registered()
+            .into_iter()
+            .find(|strategy| strategy.name == name)
+    }
+}
+
+impl Standing {
+    pub fn goal_difference(&self) -> i64 {
+        self.goals_for as i64 - self.goals_against as i64
+    }
+}
+
+/// Every entrant against every other, `rounds` times from each end, each
+/// match with its own seed counting up from `seed`.
+pub fn round_robin(entrants: usize, rounds: u32, seed: u64) -> Vec<Fixture> {
+    let mut fixtures = Vec::new();
+    for _ in 0..rounds {
+        for green in 0..entrants {
+            for red in 0..entrants {
+                if green != red {
+                    fixtures.push(Fixture {
+                        green,
+                        red,
+                        seed: seed + fixtures.len() as u64,
+                    });
+                }
+            }
+        }
+    }
+    fixtures
+}
+
+/// Plays `fixture` to full time in `scene`, which needs a `soccer` section.
+pub fn play(
+    scene: &Scene,
+    entrants: &[Strategy],
+    fixture: Fixture,
+) -> Result<MatchResult, SceneError> {
+    if scene.soccer.is_none() {
+        return Err(SceneError::invalid(
+            None,
+            "tournaments need a scene with a soccer section".to_string(),
+        ));
+    }
+    let mut world = TopDownWorld::from_scene(scene, &mut StdRng::seed_from_u64(fixture.seed))?;
+    world.ai = [(Team::Green, fixture.green), (Team::Red, fixture.red)]
+        .iter()
+        .map(|&(team, entrant)| {
+            let strategy = &entrants[entrant];
+            TeamAi::new(team, strategy.tactics, strategy.difficulty)
+        })
+        .collect();
+
+    let dt = world.physics.timestep();
+    let mut possession = [0.0; 2];
+    let mut speed_sum = [0.0; 2];
+    let mut samples = [0u32; 2];
+    loop {
+        let soccer = world
+            .soccer
+            .as_ref()
+            .expect("Tournament worlds have a match");
+        if soccer.is_finished() {
+            break;
+        }
+        if soccer.is_playing() {
+            if let Some(team) = in_possession(&world) {
+                possession[team.index()] += dt;
+            }
+            for floater in world.floaters.iter() {
+                let velocity = world.physics.try_get_vel_of(floater.get_handle());
+                if let (Some(team), Ok(velocity)) = (floater.team, velocity) {
+                    speed_sum[team.index()] += velocity.norm();
+                    samples[team.index()] += 1;
+                }
+            }
+        }
+        world.update();
+    }
+
+    let average = |team: usize| speed_sum[team] / samples[team].max(1) as f32;
+    Ok(MatchResult {
+        fixture,
+        score: world.soccer.expect("Tournament worlds have a match").score,
+        possession,
+        average_speed: [average(0), average(1)],
+    })
+}
+
+/// Plays every fixture, spread over `threads` threads, returning results in
+/// fixture order.
+pub fn run(
+    scene: &Scene,
+    entrants: &[Strategy],
+    fixtures: &[Fixture],
+    threads: usize,
+) -> Result<Vec<MatchResult>, SceneError> {
+    let queue = Arc::new(Mutex::new(0..fixtures.len()));
+    let (sender, receiver) = mpsc::channel();
+
+    let mut workers = Vec::new();
+    for _ in 0..threads.max(1).min(fixtures.len().max(1)) {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        let scene = scene.clone();
+        let entrants = entrants.to_vec();
+        let fixtures = fixtures.to_vec();
+        workers.push(thread::spawn(move || loop {
+            let next = queue.lock().expect("Fixture queue poisoned").next();
+            let position = match next {
+                Some(position) => position,
+                None => break,
+            };
+            let result = play(&scene, &entrants, fixtures[position]);
+            if sender.send((position, result)).is_err() {
+                break;
+            }
+        }));
+    }
+    drop(sender);
+
+    let mut results: Vec<Option<MatchResult>> = vec![None; fixtures.len()];
+    for (position, result) in receiver {
+        results[position] = Some(result?);
+    }
+    for worker in workers {
+        worker.join().expect("Tournament thread panicked");
+    }
+    Ok(results
+        .into_iter()
+        .map(|result| result.expect("Every fixture was played"))
+        .collect())
+}
+
+/// Totals up `results` per entrant, best first: by points, then goal
+/// difference, then goals scored.
+pub fn leaderboard(entrants: &[Strategy], results: &[MatchResult]) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = entrants
+        .iter()
+        .map(|strategy| Standing {
+            strategy: strategy.name.clone(),
+            ..Standing::default()
+        })
+        .collect();
+    let mut possessed = vec![0.0; entrants.len()];
+    let mut contested = vec![0.0; entrants.len()];
+
+    for result in results {
+        let sides = [result.fixture.green, result.fixture.red];
+        let total_possession = result.possession[0] + result.possession[1];
+        for (side, &entrant) in sides.iter().enumerate() {
+            let scored = result.score[side];
+            let conceded = result.score[1 - side];
+            let standing = &mut standings[entrant];
+            standing.played += 1;
+            standing.goals_for += scored;
+            standing.goals_against += conceded;
+            match scored.cmp(&conceded) {
+                Ordering::Greater => {
+                    standing.wins += 1;
+                    standing.points += WIN_POINTS;
+                }
+                Ordering::Equal => {
+                    standing.draws += 1;
+                    standing.points += DRAW_POINTS;
+                }
+                Ordering::Less => standing.losses += 1,
+            }
+            // Running mean, so each match counts the same.
+            standing.average_speed +=
+                (result.average_speed[side] - standing.average_speed) / standing.played as f32;
+            possessed[entrant] += result.possession[side];
+            contested[entrant] += total_possession;
+        }
+    }
+    for (entrant, standing) in standings.iter_mut().enumerate() {
+        if contested[entrant] > 0.0 {
+            standing.possession = possessed[entrant] / contested[entrant];
+        }
+    }
+
+    standings.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then(b.goal_difference().cmp(&a.goal_difference()))
+            .then(b.goals_for.cmp(&a.goals_for))
+    });
+    standings
+}
+
+pub fn write_csv<W: Write>(standings: &[Standing], out: &mut W) -> io::Result<()> {
+    writeln!(
+        out,
+        "rank,strategy,played,wins,draws,losses,goals_for,goals_against,goal_difference,points,possession,average_speed"
+    )?;
+    for (rank, standing) in standings.iter().enumerate() {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            rank + 1,
+            standing.strategy,
+            standing.played,
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.goals_for,
+            standing.goals_against,
+            standing.goal_difference(),
+            standing.points,
+            standing.possession,
+            standing.average_speed
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_markdown<W: Write>(standings: &[Standing], out: &mut W) -> io::Result<()> {
+    writeln!(
+        out,
+        "| # | Strategy | P | W | D | L | GF | GA | GD | Pts | Possession | Avg speed |"
+    )?;
+    writeln!(
+        out,
+        "|--:|----------|--:|--:|--:|--:|---:|---:|---:|----:|-----------:|----------:|"
+    )?;
+    for (rank, standing) in standings.iter().enumerate() {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} | {} | {} | {:+} | {} | {:.0}% | {:.1} |",
+            rank + 1,
+            standing.strategy,
+            standing.played,
+            standing.wins,
+            standing.draws,
+            standing.losses,
+            standing.goals_for,
+            standing.goals_against,
+            standing.goal_difference(),
+            standing.points,
+            standing.possession * 100.0,
+            standing.average_speed
+        )?;
+    }
+    Ok(())
+}
+
+/// The team whose floater is nearest the ball, if that floater is touching
+/// it or close to.
+fn in_possession(world: &TopDownWorld) -> Option<Team> {
+    let ball = world
+        .physics
+        .try_get_pos_of(world.beach_ball.get_handle())
+        .ok()?;
+    let reach = world.beach_ball.get_radius() + POSSESSION_REACH;
+    world
+        .floaters
+        .iter()
+        .filter_map(|floater| {
+            let team = floater.team?;
+            let position = world.physics.try_get_pos_of(floater.get_handle()).ok()?;
+            let gap = (position - ball).norm() - floater.get_radius();
+            Some((team, gap))
+        })
+        .filter(|&(_, gap)| gap <= reach)
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+        .map(|(team, _)| team)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(green: usize, red: usize, score: [u32; 2]) -> MatchResult {
+        MatchResult {
+            fixture: Fixture {
+                green,
+                red,
+                seed: 0,
+            },
+            score,
+            possession: [1.0, 1.0],
+            average_speed: [0.0, 0.0],
+        }
+    }
+
+    fn entrants(count: usize) -> Vec<Strategy> {
+        Strategy::registered().into_iter().take(count).collect()
+    }
+
+    fn standing<'a>(standings: &'a [Standing], strategy: &Strategy) -> &'a Standing {
+        standings
+            .iter()
+            .find(|standing| standing.strategy == strategy.name)
+            .expect("Every entrant has a standing")
+    }
+
+    #[test]
+    fn wins_and_draws_score_points() {
+        let entrants = entrants(3);
+        let results = [
+            result(0, 1, [2, 1]),
+            result(1, 2, [0, 0]),
+            result(2, 0, [3, 0]),
+        ];
+        let standings = leaderboard(&entrants, &results);
+
+        let first = standing(&standings, &entrants[0]);
+        assert_eq!((first.played, first.wins, first.losses), (2, 1, 1));
+        assert_eq!(first.points, WIN_POINTS);
+        let second = standing(&standings, &entrants[1]);
+        assert_eq!((second.draws, second.losses), (1, 1));
+        assert_eq!(second.points, DRAW_POINTS);
+        let third = standing(&standings, &entrants[2]);
+        assert_eq!(third.points, WIN_POINTS + DRAW_POINTS);
+        assert_eq!(standings[0].strategy, entrants[2].name);
+    }
+
+    #[test]
+    fn goal_difference_counts_both_ends() {
+        let entrants = entrants(2);
+        let results = [result(0, 1, [4, 1]), result(1, 0, [2, 0])];
+        let standings = leaderboard(&entrants, &results);
+
+        let first = standing(&standings, &entrants[0]);
+        assert_eq!((first.goals_for, first.goals_against), (4, 3));
+        assert_eq!(first.goal_difference(), 1);
+        assert_eq!(standing(&standings, &entrants[1]).goal_difference(), -1);
+    }
+
+    #[test]
+    fn ties_on_points_go_to_goal_difference_then_goals() {
+        let entrants = entrants(3);
+        // All three win once and lose once.
+        let results = [
+            result(0, 1, [1, 0]),
+            result(1, 2, [5, 0]),
+            result(2, 0, [3, 1]),
+        ];
+        let standings = leaderboard(&entrants, &results);
+
+        let order: Vec<&str> = standings
+            .iter()
+            .map(|standing| standing.strategy.as_str())
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                entrants[1].name.as_str(),
+                entrants[0].name.as_str(),
+                entrants[2].name.as_str(),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_robin_plays_each_pairing_from_both_ends() {
+        let fixtures = round_robin(3, 2, 10);
+        assert_eq!(fixtures.len(), 12);
+        assert!(fixtures.iter().all(|fixture| fixture.green != fixture.red));
+        let seeds: Vec<u64> = fixtures.iter().map(|fixture| fixture.seed).collect();
+        assert_eq!(seeds, (10..22).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,1132 @@
+use crate::arrival::ArrivalController;
+use crate::avoidance::{avoid, Agent, AvoidanceSettings};
+use crate::events::{Contact, PhysicsEvent};
+use crate::floater::{BeachBall, Floater, Orders, PhysicsBall};
+use crate::formation::{assign_slots, Formation, FormationMove, FORMATION_GAP, FORMATION_SPEED};
+use crate::herding::HerdSettings;
+use crate::navigation::{NavGrid, NAV_CELL};
+use crate::physics::{PhysicsError, PhysicsStruct};
+use crate::scene::{Role, Scene, SceneBody, SceneError, ShapeDesc};
+use crate::selection::{Selection, CONTROL_GROUPS};
+use crate::snapshot::{
+    BallSnapshot, FloaterSnapshot, FormationSnapshot, GoalSnapshot, MatchSnapshot, PropSnapshot,
+    SnapshotError, TopDownSnapshot, ZoneSnapshot,
+};
+use crate::soccer::{Goal, Match, MatchSettings, Team};
+use crate::steering::{ControllerKind, FloaterView, Steering, WorldView};
+use crate::team_ai::{Tactics, TeamAi};
+use crate::zones::{ZoneEvent, Zones};
+
+use nalgebra::{Isometry2, Vector2};
+use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
+use nphysics2d::math::Velocity;
+use nphysics2d::object::DefaultBodyHandle;
+use rand::Rng;
+
+use std::collections::HashMap;
+
+// Constants
+/// Furthest a kickoff spot is nudged from where it would otherwise be.
+const KICKOFF_JITTER: f32 = 20.0;
+
+/// What a body is in the top-down world, for making sense of physics events.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorldBody {
+    Floater(usize),
+    BeachBall,
+    Prop(usize),
+    Goal(Team),
+    Zone(usize),
+    /// The arena walls, or anything else the world doesn't keep track of.
+    Wall,
+}
+
+/// The top-down sandbox: a walled arena with floaters and a beach ball to push.
+pub struct TopDownWorld {
+    pub physics: PhysicsStruct,
+    pub floaters: Vec<Floater>,
+    pub beach_ball: BeachBall,
+    pub props: Vec<SceneBody>,
+    pub formations: Vec<FormationMove>,
+    pub navigation: NavGrid,
+    /// Local avoidance between floaters, when switched on.
+    pub avoidance: Option<AvoidanceSettings>,
+    /// The match being played, if this is a soccer scene.
+    pub soccer: Option<Match>,
+    /// Computer-controlled teams in the match.
+    pub ai: Vec<TeamAi>,
+    pub zones: Zones,
+    /// Who came, stayed and went from the zones during the last update.
+    pub zone_events: Vec<ZoneEvent>,
+    pub tick: u64,
+}
+
+impl WorldBody {
+    pub fn is_floater(self) -> bool {
+        matches!(self, WorldBody::Floater(_))
+    }
+
+    pub fn is_beach_ball(self) -> bool {
+        self == WorldBody::BeachBall
+    }
+
+    pub fn is_wall(self) -> bool {
+        self == WorldBody::Wall
+    }
+}
+
+impl TopDownWorld {
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
+        TopDownWorld::from_scene(&Scene::top_down(), rng).expect("Built-in scene failed to load")
+    }
+
+    pub fn from_scene<R: Rng>(scene: &Scene, rng: &mut R) -> Result<Self, SceneError> {
+        if scene.bodies_with_role(Role::BeachBall) != 1 {
+            return Err(SceneError::invalid(
+                None,
+                "a top_down scene needs exactly one BeachBall".to_string(),
+            ));
+        }
+        if scene.bodies_with_role(Role::Player) != 0 {
+            return Err(SceneError::invalid(
+                None,
+                "Player bodies are only supported in side_view scenes".to_string(),
+            ));
+        }
+
+        let (physics, bodies) = PhysicsStruct::from_scene(scene, rng)?;
+
+        let mut floaters = Vec::new();
+        let mut beach_ball = None;
+        let mut props = Vec::new();
+        for body in bodies {
+            let radius = body.shape.bounding_radius();
+            match body.role {
+                Role::Floater => {
+                    let mut floater = Floater::with_style(
+                        body.handle,
+                        radius,
+                        body.color.unwrap_or_else(Floater::color),
+                    );
+                    if let Some(arrival) = body.arrival {
+                        floater.set_controller(Box::new(ArrivalController::new(arrival)));
+                    }
+                    floaters.push(floater);
+                }
+                Role::BeachBall => {
+                    beach_ball = Some(BeachBall::with_style(
+                        body.handle,
+                        radius,
+                        body.color.unwrap_or_else(BeachBall::color),
+                    ))
+                }
+                Role::Prop => props.push(body),
+                Role::Player => (),
+            }
+        }
+
+        let navigation = NavGrid::build(&physics, NAV_CELL, nav_clearance(&floaters));
+        let mut world = TopDownWorld {
+            physics,
+            floaters,
+            beach_ball: beach_ball.expect("Beach ball was validated above"),
+            props,
+            formations: Vec::new(),
+            navigation,
+            avoidance: None,
+            soccer: None,
+            ai: Vec::new(),
+            zones: Zones::new(),
+            zone_events: Vec::new(),
+            tick: 0,
+        };
+        for desc in scene.zones.iter() {
+            let zone = world.zones.add(&mut world.physics, desc.clone());
+            if !desc.watch.is_empty() {
+                let watched = world.bodies_with_roles(&desc.watch);
+                world.zones.watch(zone, &watched);
+            }
+        }
+        if let Some(settings) = scene.soccer {
+            world.start_match(settings, rng.gen());
+        }
+        Ok(world)
+    }
+
+    /// Everything needed to rebuild the world, with the selection and
+    /// control groups. Fails if a floater, prop, zone or goal has lost its
+    /// body, since there would be nothing to save for it.
+    pub fn snapshot(&self, selection: &Selection) -> Result<TopDownSnapshot, PhysicsError> {
+        let (physics, handles) = self.physics.snapshot();
+        let indices: HashMap<DefaultBodyHandle, usize> = handles
+            .into_iter()
+            .enumerate()
+            .map(|(index, handle)| (handle, index))
+            .collect();
+        let index_of = |handle: DefaultBodyHandle| {
+            indices
+                .get(&handle)
+                .copied()
+                .ok_or(PhysicsError::MissingBody(handle))
+        };
+
+        let floaters = self
+            .floaters
+            .iter()
+            .map(|floater| {
+                Ok(FloaterSnapshot {
+                    body: index_of(floater.get_handle())?,
+                    radius: floater.get_radius(),
+                    color: floater.get_color().into(),
+                    target: floater.target.map(|target| (target.x, target.y)),
+                    waypoints: floater
+                        .orders
+                        .waypoints
+                        .iter()
+                        .map(|waypoint| (waypoint.x, waypoint.y))
+                        .collect(),
+                    patrol: floater.orders.patrol,
+                    hold: floater.orders.hold,
+                    controller: floater.controller.kind(),
+                    team: floater.team,
+                })
+            })
+            .collect::<Result<_, PhysicsError>>()?;
+        let beach_ball = BallSnapshot {
+            body: index_of(self.beach_ball.get_handle())?,
+            radius: self.beach_ball.get_radius(),
+            color: self.beach_ball.get_color().into(),
+        };
+        let props = self
+            .props
+            .iter()
+            .map(|prop| {
+                Ok(PropSnapshot {
+                    body: index_of(prop.handle)?,
+                    role: prop.role,
+                    shape: prop.shape,
+                    color: prop.color.map(|color| color.into()),
+                })
+            })
+            .collect::<Result<_, PhysicsError>>()?;
+        let floater_index: HashMap<DefaultBodyHandle, usize> = self
+            .floaters
+            .iter()
+            .enumerate()
+            .map(|(index, floater)| (floater.get_handle(), index))
+            .collect();
+        let selected = floater_indices(&floater_index, selection.selected.iter());
+        let groups = (0..CONTROL_GROUPS)
+            .map(|group| floater_indices(&floater_index, selection.group(group).iter()))
+            .collect();
+
+        let zones = self
+            .zones
+            .iter()
+            .map(|zone| {
+                Ok(ZoneSnapshot {
+                    desc: zone.desc.clone(),
+                    sensor: index_of(zone.sensor)?,
+                    watch: zone.watch.as_ref().map(|watch| {
+                        let mut watch: Vec<usize> = watch
+                            .iter()
+                            .filter_map(|handle| indices.get(handle).copied())
+                            .collect();
+                        watch.sort_unstable();
+                        watch
+                    }),
+                    inside: zone
+                        .inside()
+                        .filter_map(|handle| indices.get(handle).copied())
+                        .collect(),
+                })
+            })
+            .collect::<Result<_, PhysicsError>>()?;
+        let soccer = match &self.soccer {
+            Some(soccer) => Some(MatchSnapshot {
+                settings: soccer.settings,
+                goals: soccer
+                    .goals
+                    .iter()
+                    .map(|goal| {
+                        Ok(GoalSnapshot {
+                            defender: goal.defender,
+                            sensor: index_of(goal.sensor)?,
+                            center: (goal.center.x, goal.center.y),
+                            half_extents: (goal.half_extents.x, goal.half_extents.y),
+                        })
+                    })
+                    .collect::<Result<_, PhysicsError>>()?,
+                score: soccer.score,
+                time_left: soccer.time_left,
+                phase: soccer.phase,
+                last_scorer: soccer.last_scorer,
+                kicker: soccer.kicker,
+                kickoffs: soccer.kickoffs,
+                kickoff_seed: soccer.kickoff_seed,
+                stalled: soccer.stalled,
+            }),
+            None => None,
+        };
+
+        Ok(TopDownSnapshot {
+            physics,
+            floaters,
+            beach_ball,
+            props,
+            selected,
+            tick: self.tick,
+            groups,
+            formations: self
+                .formations
+                .iter()
+                .map(|formation| FormationSnapshot {
+                    members: formation
+                        .members
+                        .iter()
+                        .map(|&(index, offset)| (index, (offset.x, offset.y)))
+                        .collect(),
+                    anchor: (formation.anchor.x, formation.anchor.y),
+                    destination: (formation.destination.x, formation.destination.y),
+                    speed: formation.speed,
+                    slack: formation.slack,
+                })
+                .collect(),
+            avoidance: self.avoidance,
+            zones,
+            soccer,
+        })
+    }
+
+    /// Rebuilds a world from `snapshot`, along with the selection and control
+    /// groups it was taken with.
+    pub fn from_snapshot(snapshot: &TopDownSnapshot) -> Result<(Self, Selection), SnapshotError> {
+        let (physics, handles) = PhysicsStruct::from_snapshot(&snapshot.physics)?;
+        let handle_of = |index| {
+            snapshot
+                .physics
+                .check_body(index)
+                .map(|index| handles[index])
+        };
+
+        let mut floaters = Vec::new();
+        for saved in snapshot.floaters.iter() {
+            let mut floater =
+                Floater::with_style(handle_of(saved.body)?, saved.radius, saved.color.into());
+            floater.target = saved.target.map(|(x, y)| Vector2::new(x, y));
+            floater.orders = Orders {
+                waypoints: saved
+                    .waypoints
+                    .iter()
+                    .map(|&(x, y)| Vector2::new(x, y))
+                    .collect(),
+                patrol: saved.patrol,
+                hold: saved.hold,
+            };
+            floater.set_controller(saved.controller.build(floaters.len() as u64));
+            floater.team = saved.team;
+            floaters.push(floater);
+        }
+        let beach_ball = BeachBall::with_style(
+            handle_of(snapshot.beach_ball.body)?,
+            snapshot.beach_ball.radius,
+            snapshot.beach_ball.color.into(),
+        );
+        let mut props = Vec::new();
+        for saved in snapshot.props.iter() {
+            props.push(SceneBody {
+                handle: handle_of(saved.body)?,
+                role: saved.role,
+                shape: saved.shape,
+                color: saved.color.map(|color| color.into()),
+                arrival: None,
+            });
+        }
+
+        let floater_handle = |index: usize| {
+            floaters
+                .get(index)
+                .map(|floater| floater.get_handle())
+                .ok_or_else(|| SnapshotError::Invalid(format!("floater {} does not exist", index)))
+        };
+        let mut selection = Selection::new();
+        for &index in snapshot.selected.iter() {
+            selection.selected.insert(floater_handle(index)?);
+        }
+        if snapshot.groups.len() > CONTROL_GROUPS {
+            return Err(SnapshotError::Invalid(format!(
+                "there are only {} control groups, got {}",
+                CONTROL_GROUPS,
+                snapshot.groups.len()
+            )));
+        }
+        for (group, indices) in snapshot.groups.iter().enumerate() {
+            let members = indices
+                .iter()
+                .map(|&index| floater_handle(index))
+                .collect::<Result<_, _>>()?;
+            selection.set_group(group, members);
+        }
+
+        let mut formations = Vec::new();
+        for saved in snapshot.formations.iter() {
+            let mut members = Vec::new();
+            for &(index, (x, y)) in saved.members.iter() {
+                floater_handle(index)?;
+                members.push((index, Vector2::new(x, y)));
+            }
+            formations.push(FormationMove {
+                members,
+                anchor: Vector2::new(saved.anchor.0, saved.anchor.1),
+                destination: Vector2::new(saved.destination.0, saved.destination.1),
+                speed: saved.speed,
+                slack: saved.slack,
+            });
+        }
+
+        let soccer = match &snapshot.soccer {
+            Some(saved) => {
+                let mut goals = Vec::new();
+                for goal in saved.goals.iter() {
+                    goals.push(Goal {
+                        defender: goal.defender,
+                        sensor: handle_of(goal.sensor)?,
+                        center: Vector2::new(goal.center.0, goal.center.1),
+                        half_extents: Vector2::new(goal.half_extents.0, goal.half_extents.1),
+                    });
+                }
+                Some(Match {
+                    settings: saved.settings,
+                    goals,
+                    score: saved.score,
+                    time_left: saved.time_left,
+                    phase: saved.phase,
+                    last_scorer: saved.last_scorer,
+                    kicker: saved.kicker,
+                    kickoffs: saved.kickoffs,
+                    kickoff_seed: saved.kickoff_seed,
+                    stalled: saved.stalled,
+                })
+            }
+            None => None,
+        };
+
+        let ai = match &soccer {
+            Some(soccer) => team_ais(&soccer.settings),
+            None => Vec::new(),
+        };
+
+        let navigation = NavGrid::build(&physics, NAV_CELL, nav_clearance(&floaters));
+        let mut world = TopDownWorld {
+            physics,
+            floaters,
+            beach_ball,
+            props,
+            formations,
+            navigation,
+            avoidance: snapshot.avoidance,
+            soccer,
+            ai,
+            zones: Zones::new(),
+            zone_events: Vec::new(),
+            tick: snapshot.tick,
+        };
+
+        // Zone sensors are built again from their descriptions, since the
+        // physics snapshot can't hold polygons.
+        for saved in snapshot.zones.iter() {
+            saved.desc.validate().map_err(SnapshotError::Invalid)?;
+            world.physics.remove_body(handle_of(saved.sensor)?);
+            let inside = saved
+                .inside
+                .iter()
+                .map(|&index| handle_of(index))
+                .collect::<Result<Vec<_>, _>>()?;
+            let shape = saved.desc.shape_handle().expect("Zone was validated above");
+            let sensor = world.physics.create_sensor(saved.desc.position(), shape);
+            let zone = world.zones.insert(saved.desc.clone(), sensor, inside);
+            if let Some(watch) = &saved.watch {
+                let watched = watch
+                    .iter()
+                    .map(|&index| handle_of(index))
+                    .collect::<Result<Vec<_>, _>>()?;
+                world.zones.watch(zone, &watched);
+            }
+        }
+
+        Ok((world, selection))
+    }
+
+    /// Turns the world into a soccer match: puts goal sensors at each end,
+    /// splits the floaters into teams, first half Green, and lines up for
+    /// kickoff. `kickoff_seed` varies the kickoffs from match to match.
+    pub fn start_match(&mut self, settings: MatchSettings, kickoff_seed: u64) {
+        let mut goals = Vec::new();
+        for &(defender, center, half_extents) in settings.goal_areas(self.physics.dims()).iter() {
+            let sensor = self
+                .physics
+                .create_sensor(center, ShapeHandle::new(Cuboid::new(half_extents)));
+            goals.push(Goal {
+                defender,
+                sensor,
+                center,
+                half_extents,
+            });
+        }
+
+        let half = self.floaters.len() / 2;
+        for (index, floater) in self.floaters.iter_mut().enumerate() {
+            floater.set_team(if index < half { Team::Green } else { Team::Red });
+        }
+
+        self.soccer = Some(Match::new(settings, goals, kickoff_seed));
+        self.ai = team_ais(&settings);
+        self.line_up();
+    }
+
+    /// Floaters on `team`, by index.
+    pub fn team_members(&self, team: Team) -> Vec<usize> {
+        self.floaters
+            .iter()
+            .enumerate()
+            .filter(|(_, floater)| floater.team == Some(team))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Whether the computer plays floater `index`, so it isn't the player's
+    /// to order around.
+    pub fn is_ai_controlled(&self, index: usize) -> bool {
+        match self.floaters[index].team {
+            Some(team) => self.ai.iter().any(|ai| ai.team() == team),
+            None => false,
+        }
+    }
+
+    /// Every floater, beach ball and prop whose scene role is in `roles`.
+    pub fn bodies_with_roles(&self, roles: &[Role]) -> Vec<DefaultBodyHandle> {
+        let mut bodies = Vec::new();
+        if roles.contains(&Role::Floater) {
+            bodies.extend(self.floaters.iter().map(|floater| floater.get_handle()));
+        }
+        if roles.contains(&Role::BeachBall) {
+            bodies.push(self.beach_ball.get_handle());
+        }
+        bodies.extend(
+            self.props
+                .iter()
+                .filter(|prop| roles.contains(&prop.role))
+                .map(|prop| prop.handle),
+        );
+        bodies
+    }
+
+    /// Which body `handle` is.
+    pub fn identify(&self, handle: DefaultBodyHandle) -> WorldBody {
+        if handle == self.beach_ball.get_handle() {
+            return WorldBody::BeachBall;
+        }
+        if let Some(index) = self
+            .floaters
+            .iter()
+            .position(|floater| floater.get_handle() == handle)
+        {
+            return WorldBody::Floater(index);
+        }
+        if let Some(index) = self.props.iter().position(|prop| prop.handle == handle) {
+            return WorldBody::Prop(index);
+        }
+        let goal = self
+            .soccer
+            .as_ref()
+            .and_then(|soccer| soccer.goal_of(handle));
+        if let Some(goal) = goal {
+            return WorldBody::Goal(goal.defender);
+        }
+        match self.zones.zone_of(handle) {
+            Some(zone) => WorldBody::Zone(zone),
+            None => WorldBody::Wall,
+        }
+    }
+
+    /// Contacts that started during the last step between a body `first`
+    /// picks out and one `second` does, each seen from the `first` body.
+    /// `world.hits(WorldBody::is_floater, WorldBody::is_beach_ball)` finds
+    /// floaters bumping the ball.
+    pub fn hits<F, S>(&self, first: F, second: S) -> Vec<Contact>
+    where
+        F: Fn(WorldBody) -> bool,
+        S: Fn(WorldBody) -> bool,
+    {
+        self.physics
+            .events()
+            .iter()
+            .filter_map(|event| match event {
+                PhysicsEvent::ContactStarted(contact) => Some(contact),
+                _ => None,
+            })
+            .filter_map(|contact| {
+                let body1 = self.identify(contact.body1);
+                let body2 = self.identify(contact.body2);
+                if first(body1) && second(body2) {
+                    Some(*contact)
+                } else if first(body2) && second(body1) {
+                    Some(contact.flipped())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Puts the ball just inside the kicking-off team's half and each team in
+    /// a line across its own half, every spot nudged a little, with every
+    /// order dropped.
+    fn line_up(&mut self) {
+        let (kicker, mut rng) = match &self.soccer {
+            Some(soccer) => (soccer.kicker, soccer.kickoff_rng()),
+            None => return,
+        };
+        let mut jitter = || {
+            Vector2::new(
+                rng.gen_range(-KICKOFF_JITTER, KICKOFF_JITTER),
+                rng.gen_range(-KICKOFF_JITTER, KICKOFF_JITTER),
+            )
+        };
+
+        let (width, height) = self.physics.dims();
+        let ball = Vector2::new(
+            width / 2.0 - kicker.attack_direction() * width / 16.0,
+            height / 2.0,
+        ) + jitter();
+        self.physics
+            .try_set_state_of(
+                self.beach_ball.get_handle(),
+                Isometry2::translation(ball.x, ball.y),
+                Velocity::zero(),
+            )
+            .ok();
+
+        for &team in [Team::Green, Team::Red].iter() {
+            let members = self.team_members(team);
+            let x = width / 2.0 - team.attack_direction() * width / 4.0;
+            for (place, &index) in members.iter().enumerate() {
+                let y = height * (place + 1) as f32 / (members.len() + 1) as f32;
+                let spot = Vector2::new(x, y) + jitter();
+                let floater = &mut self.floaters[index];
+                self.physics
+                    .try_set_state_of(
+                        floater.get_handle(),
+                        Isometry2::translation(spot.x, spot.y),
+                        Velocity::zero(),
+                    )
+                    .ok();
+                floater.target = None;
+                floater.orders = Orders::default();
+                floater.path.clear();
+                floater.controller.reset();
+            }
+        }
+        self.formations.clear();
+        for ai in self.ai.iter_mut() {
+            ai.reset();
+        }
+        self.physics.sync_geometry();
+    }
+
+    /// Places a fixed obstacle and replans around it.
+    pub fn add_obstacle(&mut self, position: Vector2<f32>, shape: ShapeDesc) -> DefaultBodyHandle {
+        let shape_handle = match shape {
+            ShapeDesc::Ball { radius } => ShapeHandle::new(Ball::new(radius)),
+            ShapeDesc::Cuboid {
+                half_width,
+                half_height,
+            } => ShapeHandle::new(Cuboid::new(Vector2::new(half_width, half_height))),
+        };
+        let handle = self.physics.create_fixed(position, shape_handle);
+        self.props.push(SceneBody {
+            handle,
+            role: Role::Prop,
+            shape,
+            color: None,
+            arrival: None,
+        });
+        self.physics.sync_geometry();
+        self.rebuild_navigation();
+        handle
+    }
+
+    /// Removes the fixed prop covering `point`, if any. The arena walls
+    /// aren't props, so they stay.
+    pub fn remove_obstacle_at(&mut self, point: Vector2<f32>) -> bool {
+        let handle = match self.physics.fixed_body_at(point) {
+            Some(handle) => handle,
+            None => return false,
+        };
+        let before = self.props.len();
+        self.props.retain(|prop| prop.handle != handle);
+        if self.props.len() == before {
+            return false;
+        }
+        self.physics.remove_body(handle);
+        self.rebuild_navigation();
+        true
+    }
+
+    /// Rebuilds the navigation grid after static colliders change. Floaters
+    /// notice the new revision and plan their paths again.
+    pub fn rebuild_navigation(&mut self) {
+        let rebuilt = NavGrid::build(&self.physics, NAV_CELL, nav_clearance(&self.floaters));
+        self.navigation.replace(rebuilt);
+    }
+
+    /// Where each of the floaters `members` (indices into `floaters`) would
+    /// stand in `formation` around `destination`. Without a `facing` the
+    /// formation faces the way the group is heading.
+    pub fn formation_slots(
+        &self,
+        members: &[usize],
+        destination: Vector2<f32>,
+        facing: Option<Vector2<f32>>,
+        formation: Formation,
+    ) -> Vec<(usize, Vector2<f32>)> {
+        let (_, offsets) = self.formation_offsets(members, destination, facing, formation);
+        offsets
+            .into_iter()
+            .map(|(index, offset)| (index, destination + offset))
+            .collect()
+    }
+
+    /// Marches the floaters `members` to `destination` in `formation`. They
+    /// form up where they are first, then move together.
+    pub fn move_in_formation(
+        &mut self,
+        members: &[usize],
+        destination: Vector2<f32>,
+        facing: Option<Vector2<f32>>,
+        formation: Formation,
+    ) {
+        let (centroid, offsets) = self.formation_offsets(members, destination, facing, formation);
+        for &index in members {
+            self.leave_formation(index);
+        }
+        for &(index, offset) in offsets.iter() {
+            self.floaters[index].set_target(centroid + offset);
+        }
+
+        let spacing = self.formation_spacing(members);
+        self.formations.push(FormationMove {
+            members: offsets,
+            anchor: centroid,
+            destination,
+            speed: FORMATION_SPEED,
+            slack: spacing,
+        });
+    }
+
+    /// Sets the floaters `members` to herding the beach ball to `goal`, each
+    /// from its own slot behind the ball. With `queue` the goal comes after
+    /// the ones already given.
+    pub fn herd_ball(&mut self, members: &[usize], goal: Vector2<f32>, queue: bool) {
+        let radius = members
+            .iter()
+            .map(|&index| self.floaters[index].get_radius())
+            .fold(0.0, f32::max);
+        let slots = HerdSettings::slots(members.len(), radius, self.beach_ball.get_radius());
+        for (&index, slot) in members.iter().zip(slots) {
+            self.leave_formation(index);
+            let floater = &mut self.floaters[index];
+            let settings = HerdSettings {
+                slot,
+                ..HerdSettings::default()
+            };
+            floater.set_controller(ControllerKind::Herd(settings).build(index as u64));
+            if queue {
+                floater.queue_waypoint(goal);
+            } else {
+                floater.set_target(goal);
+            }
+        }
+    }
+
+    /// Puts floater `index` back on the default controller if it was herding
+    /// the ball, so a plain move order is walked rather than pushed to.
+    pub fn stop_herding(&mut self, index: usize) {
+        let floater = &mut self.floaters[index];
+        if let ControllerKind::Herd(_) = floater.controller.kind() {
+            floater.set_controller(ControllerKind::default().build(index as u64));
+        }
+    }
+
+    /// Takes floater `index` out of whatever formation it is marching in.
+    pub fn leave_formation(&mut self, index: usize) {
+        for formation in self.formations.iter_mut() {
+            formation.members.retain(|&(member, _)| member != index);
+        }
+        self.formations
+            .retain(|formation| !formation.members.is_empty());
+    }
+
+    fn formation_spacing(&self, members: &[usize]) -> f32 {
+        let radius = members
+            .iter()
+            .map(|&index| self.floaters[index].get_radius())
+            .fold(0.0, f32::max);
+        radius * 2.0 + FORMATION_GAP
+    }
+
+    /// The group's centroid and each member's slot offset from the
+    /// formation's centre. Members whose bodies are gone are left out.
+    fn formation_offsets(
+        &self,
+        members: &[usize],
+        destination: Vector2<f32>,
+        facing: Option<Vector2<f32>>,
+        formation: Formation,
+    ) -> (Vector2<f32>, Vec<(usize, Vector2<f32>)>) {
+        let (members, positions): (Vec<usize>, Vec<Vector2<f32>>) = members
+            .iter()
+            .filter_map(|&index| {
+                let handle = self.floaters[index].get_handle();
+                let position = self.physics.try_get_pos_of(handle).ok()?;
+                Some((index, position))
+            })
+            .unzip();
+        let centroid = positions
+            .iter()
+            .fold(Vector2::zeros(), |sum, pos| sum + pos)
+            / members.len().max(1) as f32;
+
+        let facing = facing
+            .filter(|facing| facing.norm() > 0.0)
+            .unwrap_or(destination - centroid);
+        let facing = if facing.norm() > 0.0 {
+            facing.normalize()
+        } else {
+            Vector2::new(1.0, 0.0)
+        };
+
+        let slots = formation.slots(members.len(), self.formation_spacing(&members), facing);
+        let relative: Vec<Vector2<f32>> = positions.iter().map(|pos| pos - centroid).collect();
+        let assignment = assign_slots(&relative, &slots);
+
+        let offsets = members
+            .iter()
+            .zip(assignment)
+            .map(|(&index, slot)| (index, slots[slot]))
+            .collect();
+        (centroid, offsets)
+    }
+
+    /// Lets computer teams give their orders, then each floater's controller
+    /// push it toward the next corner of its path, bending the pushes around
+    /// neighbours if avoidance is on. Moves on to the next waypoint when a
+    /// target is reached, then advances the physics one step.
+    pub fn update(&mut self) {
+        if matches!(&self.soccer, Some(soccer) if soccer.is_playing()) {
+            let dt = self.physics.timestep();
+            let mut ai = std::mem::take(&mut self.ai);
+            for team in ai.iter_mut() {
+                team.update(self, dt);
+            }
+            self.ai = ai;
+        }
+
+        if !self.formations.is_empty() {
+            // Members whose bodies are gone drop out, so the stand-in
+            // positions below are never read.
+            let (physics, floaters) = (&self.physics, &self.floaters);
+            for formation in self.formations.iter_mut() {
+                formation
+                    .members
+                    .retain(|&(index, _)| physics.contains_body(floaters[index].get_handle()));
+            }
+            let positions: Vec<Vector2<f32>> = self
+                .floaters
+                .iter()
+                .map(|floater| {
+                    self.physics
+                        .try_get_pos_of(floater.get_handle())
+                        .unwrap_or_else(|_| Vector2::zeros())
+                })
+                .collect();
+            let dt = self.physics.timestep();
+            for formation in self.formations.iter_mut() {
+                for (index, target) in formation.advance(&positions, dt) {
+                    self.floaters[index].target = Some(target);
+                }
+            }
+            self.formations.retain(|formation| !formation.is_finished());
+        }
+
+        let view = WorldView {
+            physics: &self.physics,
+            beach_ball: self.beach_ball.get_handle(),
+            ball_radius: self.beach_ball.get_radius(),
+            dt: self.physics.timestep(),
+        };
+
+        let frozen = matches!(&self.soccer, Some(soccer) if !soccer.is_playing());
+        let mut agents = Vec::new();
+        for floater in self.floaters.iter_mut() {
+            let handle = floater.get_handle();
+            // Floaters whose bodies were removed sit the step out.
+            let (position, velocity) = match (
+                view.physics.try_get_pos_of(handle),
+                view.physics.try_get_vel_of(handle),
+            ) {
+                (Ok(position), Ok(velocity)) => (position, velocity),
+                _ => continue,
+            };
+            let target = match floater.target {
+                Some(goal) if floater.controller.follows_paths() => {
+                    Some(floater.path.next_point(&self.navigation, position, goal))
+                }
+                target => {
+                    floater.path.clear();
+                    target
+                }
+            };
+            let state = FloaterView {
+                handle,
+                position,
+                velocity,
+                radius: floater.get_radius(),
+                target,
+            };
+            // Everyone waits for kickoff, and stops at full time.
+            let steering = if frozen {
+                Steering::Idle
+            } else {
+                floater.controller.compute_force(&state, &view)
+            };
+            let force = match steering {
+                Steering::Force(force) => Some(force),
+                Steering::Arrived => {
+                    if !floater.path.advance() {
+                        floater.reached_target();
+                    }
+                    None
+                }
+                Steering::Idle => None,
+            };
+            agents.push(Agent {
+                handle,
+                radius: floater.get_radius(),
+                force,
+            });
+        }
+
+        let forces = match &self.avoidance {
+            Some(settings) => avoid(settings, &self.physics, &agents, view.dt),
+            None => agents
+                .iter()
+                .filter_map(|agent| agent.force.map(|force| (agent.handle, force)))
+                .collect(),
+        };
+
+        for (handle, force) in forces {
+            self.physics.try_apply_force(handle, force).ok();
+        }
+
+        self.physics.step();
+        self.zone_events = self.zones.update(&self.physics);
+        self.referee();
+        self.tick += 1;
+    }
+
+    /// Runs the match clock and awards a goal when the beach ball enters a
+    /// goal sensor during play. Kicks off again if the ball gets stuck.
+    fn referee(&mut self) {
+        let ball = self.beach_ball.get_handle();
+        let ball_speed = self
+            .physics
+            .try_get_vel_of(ball)
+            .map_or(0.0, |velocity| velocity.norm());
+        let dt = self.physics.timestep();
+        let soccer = match &mut self.soccer {
+            Some(soccer) => soccer,
+            None => return,
+        };
+
+        let scorer = if soccer.is_playing() {
+            self.physics
+                .sensor_entries()
+                .into_iter()
+                .filter(|&(_, body)| body == ball)
+                .find_map(|(sensor, _)| soccer.goal_of(sensor))
+                .map(|goal| goal.defender.opponent())
+        } else {
+            None
+        };
+        match scorer {
+            Some(team) => {
+                soccer.goal_scored(team);
+                self.line_up();
+            }
+            None => {
+                soccer.tick(dt);
+                if soccer.ball_stalled(ball_speed, dt) {
+                    soccer.restart();
+                    self.line_up();
+                }
+            }
+        }
+    }
+}
+
+/// A computer player for each team `settings` hands to the computer.
+fn team_ais(settings: &MatchSettings) -> Vec<TeamAi> {
+    [Team::Green, Team::Red]
+        .iter()
+        .filter_map(|&team| {
+            settings
+                .ai_for(team)
+                .map(|difficulty| TeamAi::new(team, Tactics::Roles, difficulty))
+        })
+        .collect()
+}
+
+/// How far the navigation grid keeps paths from static colliders: enough for
+/// the biggest floater to pass.
+fn nav_clearance(floaters: &[Floater]) -> f32 {
+    floaters
+        .iter()
+        .map(|floater| floater.get_radius())
+        .fold(0.0, f32::max)
+}
+
+/// Sorted floater indices of `handles`, for saving selections.
+fn floater_indices<'a, I: Iterator<Item = &'a DefaultBodyHandle>>(
+    floater_index: &HashMap<DefaultBodyHandle, usize>,
+    handles: I,
+) -> Vec<usize> {
+    let mut indices: Vec<usize> = handles
+        .filter_map(|handle| floater_index.get(handle).copied())
+        .collect();
+    indices.sort_unstable();
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::headless::{self, BodyKind};
+    use crate::soccer::MatchPhase;
+    use crate::steering::Flee;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn floaters_whose_bodies_are_gone_are_skipped() {
+        let mut world = TopDownWorld::new(&mut StdRng::seed_from_u64(0));
+        let gone = world.floaters[0].get_handle();
+        world.physics.remove_body(gone);
+        let start: Vec<Option<Vector2<f32>>> = world
+            .floaters
+            .iter()
+            .map(|floater| world.physics.try_get_pos_of(floater.get_handle()).ok())
+            .collect();
+
+        world.floaters[1].set_target(Vector2::new(100.0, 100.0));
+        world.move_in_formation(
+            &[0, 2, 3],
+            Vector2::new(400.0, 400.0),
+            None,
+            Formation::Grid,
+        );
+        world.herd_ball(&[0, 4], Vector2::new(600.0, 300.0), false);
+        for floater in world.floaters[5..].iter_mut() {
+            floater.set_target(Vector2::new(400.0, 300.0));
+        }
+        for _ in 0..10 {
+            world.update();
+        }
+
+        let states = headless::body_states(&world);
+        assert!(!states
+            .iter()
+            .any(|state| state.kind == BodyKind::Floater && state.index == 0));
+        for index in 1..world.floaters.len() {
+            let state = states
+                .iter()
+                .find(|state| state.kind == BodyKind::Floater && state.index == index)
+                .expect("Floaters with bodies are reported");
+            let start = start[index].expect("Only floater 0 was removed");
+            assert!(
+                (state.position - start).norm() > 0.1,
+                "floater {} didn't move",
+                index
+            );
+        }
+        assert!(world
+            .formations
+            .iter()
+            .all(|formation| formation.members.iter().all(|&(index, _)| index != 0)));
+    }
+
+    #[test]
+    fn snapshots_report_floaters_whose_bodies_are_gone() {
+        let mut world = TopDownWorld::new(&mut StdRng::seed_from_u64(0));
+        assert!(world.snapshot(&Selection::new()).is_ok());
+
+        let gone = world.floaters[3].get_handle();
+        world.physics.remove_body(gone);
+        match world.snapshot(&Selection::new()) {
+            Err(PhysicsError::MissingBody(handle)) => assert_eq!(handle, gone),
+            other => panic!("expected a missing body, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn herders_go_back_to_arriving_when_they_stop_herding() {
+        let mut world = TopDownWorld::new(&mut StdRng::seed_from_u64(0));
+        let fleeing = ControllerKind::Flee(Flee::default());
+        world.floaters[2].set_controller(fleeing.build(2));
+        world.herd_ball(&[0, 1], Vector2::new(600.0, 300.0), false);
+        assert!(matches!(
+            world.floaters[0].controller.kind(),
+            ControllerKind::Herd(_)
+        ));
+
+        for index in 0..3 {
+            world.stop_herding(index);
+        }
+        assert_eq!(
+            world.floaters[0].controller.kind(),
+            ControllerKind::default()
+        );
+        assert_eq!(
+            world.floaters[1].controller.kind(),
+            ControllerKind::default()
+        );
+        assert_eq!(world.floaters[2].controller.kind(), fleeing);
+    }
+
+    #[test]
+    fn ball_in_a_goal_scores_for_the_other_side_and_kicks_off_again() {
+        let scene = Scene::soccer();
+        let mut world = TopDownWorld::from_scene(&scene, &mut StdRng::seed_from_u64(0))
+            .expect("Built-in soccer scene is invalid");
+        world.soccer.as_mut().unwrap().phase = MatchPhase::Playing;
+
+        let right_goal = world.soccer.as_ref().unwrap().goals[1];
+        assert_eq!(right_goal.defender, Team::Red);
+        world.physics.set_state_of(
+            world.beach_ball.get_handle(),
+            Isometry2::new(right_goal.center, 0.0),
+            Velocity::zero(),
+        );
+        world.update();
+
+        let soccer = world.soccer.as_ref().unwrap();
+        assert_eq!(soccer.score, [1, 0]);
+        assert_eq!(soccer.last_scorer, Some(Team::Green));
+        assert_eq!(soccer.kicker, Team::Red);
+        assert!(matches!(soccer.phase, MatchPhase::Kickoff { .. }));
+        let ball = world.physics.get_pos_of(world.beach_ball.get_handle());
+        assert!((ball - right_goal.center).norm() > right_goal.half_extents.norm());
+    }
+}
@@ -0,0 +1,426 @@
+use crate::events::PhysicsEvent;
+use crate::physics::PhysicsStruct;
+use crate::scene::{is_positive, Role};
+
+use ggez::graphics::Color;
+use nalgebra::{Point2, Vector2};
+use ncollide2d::shape::{Ball, ConvexPolygon, Cuboid, ShapeHandle};
+use nphysics2d::object::DefaultBodyHandle;
+use serde::{Deserialize, Serialize};
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+// Constants
+/// Narrowest a polygon zone can be, so its points aren't just in a line.
+const MIN_ZONE_WIDTH: f32 = 1e-3;
+
+// Structures & enums
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ZoneShape {
+    Ball {
+        radius: f32,
+    },
+    Cuboid {
+        half_width: f32,
+        half_height: f32,
+    },
+    /// The convex hull of `points`, relative to the zone's position.
+    Polygon {
+        points: Vec<(f32, f32)>,
+    },
+}
+
+/// A trigger region: bodies pass through it, but entering, staying in and
+/// leaving it are reported.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ZoneDesc {
+    pub name: String,
+    pub shape: ZoneShape,
+    pub position: (f32, f32),
+    /// Outline color in the debug overlay.
+    #[serde(default)]
+    pub color: Option<(f32, f32, f32, f32)>,
+    /// Which kinds of body to report when loaded from a scene; every body
+    /// if empty.
+    #[serde(default)]
+    pub watch: Vec<Role>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ZoneEventKind {
+    Enter,
+    /// Still inside after a step it didn't enter on.
+    Stay,
+    Exit,
+}
+
+/// A body coming, staying or going from zone `zone`, by index.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZoneEvent {
+    pub zone: usize,
+    pub body: DefaultBodyHandle,
+    pub kind: ZoneEventKind,
+}
+
+pub struct Zone {
+    pub desc: ZoneDesc,
+    pub sensor: DefaultBodyHandle,
+    /// Only these bodies are reported, or every body if `None`.
+    pub watch: Option<HashSet<DefaultBodyHandle>>,
+    /// In the order they came in, so reports don't depend on hashing.
+    inside: Vec<DefaultBodyHandle>,
+}
+
+/// Every trigger zone in a world, and who's in them.
+#[derive(Default)]
+pub struct Zones {
+    zones: Vec<Zone>,
+}
+
+impl ZoneDesc {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.position.0.is_finite() && self.position.1.is_finite()) {
+            return Err(format!("zone {} needs a finite position", self.name));
+        }
+        match &self.shape {
+            ZoneShape::Ball { radius } if !is_positive(*radius) => {
+                Err(format!("zone {} needs a positive radius", self.name))
+            }
+            ZoneShape::Cuboid {
+                half_width,
+                half_height,
+            } if !(is_positive(*half_width) && is_positive(*half_height)) => {
+                Err(format!("zone {} needs a positive size", self.name))
+            }
+            ZoneShape::Polygon { .. } if self.shape_handle().is_none() => Err(format!(
+                "zone {} needs at least three points that aren't all in a line",
+                self.name
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The collider shape, or `None` for a polygon with no area.
+    pub fn shape_handle(&self) -> Option<ShapeHandle<f32>> {
+        match &self.shape {
+            ZoneShape::Ball { radius } => Some(ShapeHandle::new(Ball::new(*radius))),
+            ZoneShape::Cuboid {
+                half_width,
+                half_height,
+            } => Some(ShapeHandle::new(Cuboid::new(Vector2::new(
+                *half_width,
+                *half_height,
+            )))),
+            ZoneShape::Polygon { points } => {
+                let points: Vec<Point2<f32>> =
+                    points.iter().map(|&(x, y)| Point2::new(x, y)).collect();
+                // ncollide makes a hull of points in a line, so check first.
+                if !encloses_area(&points) {
+                    return None;
+                }
+                ConvexPolygon::try_from_points(&points).map(ShapeHandle::new)
+            }
+        }
+    }
+
+    pub fn position(&self) -> Vector2<f32> {
+        Vector2::new(self.position.0, self.position.1)
+    }
+
+    pub fn color(&self) -> Option<Color> {
+        self.color.map(|(r, g, b, a)| Color::new(r, g, b, a))
+    }
+}
+
+impl Zone {
+    /// Bodies in the zone as of the last update, watched or not.
+    pub fn inside(&self) -> impl Iterator<Item = &DefaultBodyHandle> {
+        self.inside.iter()
+    }
+
+    pub fn contains(&self, body: DefaultBodyHandle) -> bool {
+        self.inside.contains(&body)
+    }
+
+    fn watches(&self, body: DefaultBodyHandle) -> bool {
+        match &self.watch {
+            Some(watch) => watch.contains(&body),
+            None => true,
+        }
+    }
+}
+
+impl Zones {
+    pub fn new() -> Self {
+        Zones::default()
+    }
+
+    /// Adds a sensor for `desc` to `physics` and returns the zone's index.
+    /// `desc` should have passed [`ZoneDesc::validate`].
+    pub fn add(&mut self, physics: &mut PhysicsStruct, desc: ZoneDesc) -> usize {
+        let shape = desc.shape_handle().expect("Zone shape was validated");
+        let sensor = physics.create_sensor(desc.position(), shape);
+        self.insert(desc, sensor, Vec::new())
+    }
+
+    /// Takes over a sensor that's already in the world, such as one restored
+    /// from a snapshot, with the bodies that were inside it.
+    pub fn insert(
+        &mut self,
+        desc: ZoneDesc,
+        sensor: DefaultBodyHandle,
+        inside: Vec<DefaultBodyHandle>,
+    ) -> usize {
+        self.zones.push(Zone {
+            desc,
+            sensor,
+            watch: None,
+            inside,
+        });
+        self.zones.len() - 1
+    }
+
+    /// Puts back who was in zone `zone`, as when rewinding, without
+    /// reporting anyone entering or leaving.
+    pub fn set_inside(&mut self, zone: usize, inside: Vec<DefaultBodyHandle>) {
+        self.zones[zone].inside = inside;
+    }
+
+    /// Reports only `bodies` from zone `zone` from now on.
+    pub fn watch(&mut self, zone: usize, bodies: &[DefaultBodyHandle]) {
+        self.zones[zone].watch = Some(bodies.iter().copied().collect());
+    }
+
+    /// Reports every body from zone `zone` again.
+    pub fn watch_all(&mut self, zone: usize) {
+        self.zones[zone].watch = None;
+    }
+
+    pub fn get(&self, zone: usize) -> &Zone {
+        &self.zones[zone]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Zone> {
+        self.zones.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.zones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    /// The index of the zone called `name`.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.zones.iter().position(|zone| zone.desc.name == name)
+    }
+
+    /// The zone whose sensor is `sensor`.
+    pub fn zone_of(&self, sensor: DefaultBodyHandle) -> Option<usize> {
+        self.zones.iter().position(|zone| zone.sensor == sensor)
+    }
+
+    /// Works out who came, stayed and went from `physics`'s events for the
+    /// step just taken. Call once after every step. Bodies removed from the
+    /// world count as leaving.
+    pub fn update(&mut self, physics: &PhysicsStruct) -> Vec<ZoneEvent> {
+        let mut entered = Vec::new();
+        let mut exited = Vec::new();
+        for event in physics.events() {
+            match *event {
+                PhysicsEvent::SensorEntered { sensor, body } => {
+                    if let Some(zone) = self.zone_of(sensor) {
+                        entered.push((zone, body));
+                    }
+                }
+                PhysicsEvent::SensorExited { sensor, body } => {
+                    if let Some(zone) = self.zone_of(sensor) {
+                        exited.push((zone, body));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut events = Vec::new();
+        for (index, zone) in self.zones.iter_mut().enumerate() {
+            let mut fresh = Vec::new();
+            for &(_, body) in entered.iter().filter(|&&(entry, _)| entry == index) {
+                // Already inside, say after a snapshot rebuilt the sensor.
+                if !zone.inside.contains(&body) {
+                    zone.inside.push(body);
+                    fresh.push(body);
+                }
+            }
+            let mut gone: Vec<DefaultBodyHandle> = exited
+                .iter()
+                .filter(|&&(exit, _)| exit == index)
+                .map(|&(_, body)| body)
+                .collect();
+            // Removing a body reports its exits too, so don't count it twice.
+            for &body in zone.inside.iter() {
+                if physics.bodies.get(body).is_none() && !gone.contains(&body) {
+                    gone.push(body);
+                }
+            }
+
+            let mut report = |body: DefaultBodyHandle, kind: ZoneEventKind| {
+                if zone.watches(body) {
+                    events.push(ZoneEvent {
+                        zone: index,
+                        body,
+                        kind,
+                    });
+                }
+            };
+            for &body in fresh.iter() {
+                report(body, ZoneEventKind::Enter);
+            }
+            for &body in zone.inside.iter() {
+                if !fresh.contains(&body) && !gone.contains(&body) {
+                    report(body, ZoneEventKind::Stay);
+                }
+            }
+            for &body in gone.iter() {
+                if zone.inside.contains(&body) {
+                    report(body, ZoneEventKind::Exit);
+                }
+            }
+            zone.inside.retain(|body| !gone.contains(body));
+        }
+        events
+    }
+}
+
+/// Whether `points` don't all lie on one line, with some room to spare.
+fn encloses_area(points: &[Point2<f32>]) -> bool {
+    let first = match points.first() {
+        Some(first) => *first,
+        None => return false,
+    };
+    let span = points
+        .iter()
+        .map(|point| point - first)
+        .max_by(|a, b| a.norm().partial_cmp(&b.norm()).unwrap_or(Ordering::Equal))
+        .unwrap_or_else(Vector2::zeros);
+    let length = span.norm();
+    length > MIN_ZONE_WIDTH
+        && points.iter().any(|point| {
+            let offset = point - first;
+            (span.x * offset.y - span.y * offset.x).abs() / length > MIN_ZONE_WIDTH
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nalgebra::Isometry2;
+    use nphysics2d::math::Velocity;
+
+    fn square(name: &str, position: (f32, f32)) -> ZoneDesc {
+        ZoneDesc {
+            name: name.to_string(),
+            shape: ZoneShape::Cuboid {
+                half_width: 30.0,
+                half_height: 30.0,
+            },
+            position,
+            color: None,
+            watch: Vec::new(),
+        }
+    }
+
+    fn kinds(events: &[ZoneEvent], body: DefaultBodyHandle) -> Vec<ZoneEventKind> {
+        events
+            .iter()
+            .filter(|event| event.body == body)
+            .map(|event| event.kind)
+            .collect()
+    }
+
+    fn move_to(physics: &mut PhysicsStruct, body: DefaultBodyHandle, x: f32, y: f32) {
+        physics.set_state_of(
+            body,
+            Isometry2::new(Vector2::new(x, y), 0.0),
+            Velocity::zero(),
+        );
+    }
+
+    #[test]
+    fn bodies_enter_stay_and_exit() {
+        let mut physics = PhysicsStruct::new((400.0, 400.0));
+        let mut zones = Zones::new();
+        let zone = zones.add(&mut physics, square("pad", (200.0, 200.0)));
+        let ball = physics.create_ball(5.0, Vector2::new(100.0, 200.0), 1.0);
+
+        let mut seen = Vec::new();
+        for position in [100.0, 200.0, 200.0, 200.0, 100.0, 100.0].iter() {
+            move_to(&mut physics, ball, *position, 200.0);
+            physics.step();
+            seen.push(kinds(&zones.update(&physics), ball));
+        }
+        use ZoneEventKind::*;
+        assert_eq!(
+            seen,
+            vec![
+                vec![],
+                vec![Enter],
+                vec![Stay],
+                vec![Stay],
+                vec![Exit],
+                vec![]
+            ]
+        );
+        assert!(!zones.get(zone).contains(ball));
+    }
+
+    #[test]
+    fn only_watched_bodies_are_reported() {
+        let mut physics = PhysicsStruct::new((400.0, 400.0));
+        let mut zones = Zones::new();
+        let zone = zones.add(&mut physics, square("pad", (200.0, 200.0)));
+        let watched = physics.create_ball(5.0, Vector2::new(190.0, 200.0), 1.0);
+        let ignored = physics.create_ball(5.0, Vector2::new(210.0, 200.0), 1.0);
+        zones.watch(zone, &[watched]);
+
+        physics.step();
+        let events = zones.update(&physics);
+        assert_eq!(kinds(&events, watched), vec![ZoneEventKind::Enter]);
+        assert!(kinds(&events, ignored).is_empty());
+        // Unwatched bodies are still tracked, just not reported.
+        assert!(zones.get(zone).contains(ignored));
+
+        physics.remove_body(watched);
+        physics.remove_body(ignored);
+        physics.step();
+        let events = zones.update(&physics);
+        assert_eq!(kinds(&events, watched), vec![ZoneEventKind::Exit]);
+        assert!(kinds(&events, ignored).is_empty());
+        assert_eq!(zones.get(zone).inside().count(), 0);
+    }
+
+    #[test]
+    fn polygons_need_some_area() {
+        let mut zone = square("strip", (200.0, 200.0));
+        zone.shape = ZoneShape::Polygon {
+            points: vec![(0.0, 0.0), (10.0, 10.0), (20.0, 20.0)],
+        };
+        assert!(zone.validate().is_err());
+        assert!(zone.shape_handle().is_none());
+
+        zone.shape = ZoneShape::Polygon {
+            points: vec![(0.0, 0.0), (10.0, 10.0)],
+        };
+        assert!(zone.validate().is_err());
+
+        zone.shape = ZoneShape::Polygon {
+            points: vec![(0.0, 0.0), (20.0, 0.0), (10.0, 10.0)],
+        };
+        assert!(zone.validate().is_ok());
+    }
+}